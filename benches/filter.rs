@@ -0,0 +1,72 @@
+//! Matching app names with [`Matcher`], over a generated list of names
+//!
+//! Run with `cargo bench --bench filter`
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use gyr::matcher::Matcher;
+
+/// Names to match, like the ones `scripts/bench_filter.sh` generates
+const NAMES: usize = 10_000;
+
+/// Typed one character at a time
+const QUERY: &str = "application 42";
+
+/// Names of the fixture apps
+fn names() -> Arc<[String]> {
+    let kinds = ["Editor", "Browser", "Terminal", "Player"];
+    (0..NAMES)
+        .map(|i| format!("Application {i} {}", kinds[i % kinds.len()]))
+        .collect()
+}
+
+/// Matches `query`, among `candidates` if set, returning the indices of the matches
+fn run(
+    matcher: &Matcher,
+    names: &Arc<[String]>,
+    query: &str,
+    exact: bool,
+    candidates: Option<Vec<usize>>,
+) -> Vec<usize> {
+    let generation = matcher.start(names.clone(), query, exact, candidates);
+    let mut matches = vec![];
+    while let Some(batch) = matcher.recv(None) {
+        if batch.generation != generation {
+            continue;
+        }
+        matches.extend(batch.matches.into_iter().map(|(i, _)| i));
+        if batch.done {
+            break;
+        }
+    }
+    matches
+}
+
+fn filter(c: &mut Criterion) {
+    let names = names();
+    let matcher = Matcher::new();
+
+    let mut group = c.benchmark_group("matcher");
+    for exact in [false, true] {
+        let id = if exact { "exact" } else { "fuzzy" };
+        group.bench_function(BenchmarkId::new("all", id), |b| {
+            b.iter(|| run(&matcher, &names, QUERY, exact, None));
+        });
+    }
+    // Every keystroke only scores the matches of the previous one
+    group.bench_function("typing", |b| {
+        b.iter(|| {
+            let mut candidates = None;
+            for (end, _) in QUERY.char_indices().skip(1) {
+                candidates = Some(run(&matcher, &names, &QUERY[..end], false, candidates));
+            }
+            run(&matcher, &names, QUERY, false, candidates)
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, filter);
+criterion_main!(benches);
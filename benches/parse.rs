@@ -0,0 +1,80 @@
+//! Parsing desktop files with [`xdg::parse_all`], against parsing them one by one with
+//! [`xdg::parse_file`]
+//!
+//! The files are generated from the ones in `benches/fixtures`, each with its own name. Run with
+//! `cargo bench --bench parse`
+
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use gyr::xdg;
+
+/// Files parsed per iteration
+const FILES: usize = 1000;
+
+/// Writes `FILES` desktop files to `dir`, made from the fixtures with a different name each
+fn fixtures(dir: &Path) -> Vec<PathBuf> {
+    let templates = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/fixtures");
+    let mut templates: Vec<PathBuf> = fs::read_dir(templates)
+        .expect("Couldn't read the fixtures")
+        .map(|entry| entry.expect("Couldn't read the fixtures").path())
+        .collect();
+    templates.sort();
+    let templates: Vec<String> = templates
+        .iter()
+        .map(|file| fs::read_to_string(file).expect("Couldn't read the fixtures"))
+        .collect();
+
+    fs::create_dir_all(dir).expect("Couldn't create the fixtures");
+    (0..FILES)
+        .map(|i| {
+            let contents = templates[i % templates.len()]
+                .lines()
+                .map(|line| match line.split_once('=') {
+                    Some(("Name", _)) => format!("{line} {i}\n"),
+                    _ => format!("{line}\n"),
+                })
+                .collect::<String>();
+            let file = dir.join(format!("app-{i:04}.desktop"));
+            fs::write(&file, contents).expect("Couldn't write the fixtures");
+            file
+        })
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("gyr-bench-parse-{}", process::id()));
+    let files = fixtures(&dir);
+
+    let mut group = c.benchmark_group("xdg");
+    group.throughput(Throughput::Elements(FILES as u64));
+    group.bench_function("parse_file", |b| {
+        b.iter(|| {
+            let mut apps = 0;
+            for file in &files {
+                apps += black_box(xdg::parse_file(file)).len();
+            }
+            apps
+        });
+    });
+    group.bench_function("parse_all", |b| {
+        b.iter(|| {
+            let mut apps = 0;
+            xdg::parse_all(files.clone(), &xdg::parse_file, |parsed| {
+                apps += black_box(parsed).len();
+                true
+            });
+            apps
+        });
+    });
+    group.finish();
+
+    fs::remove_dir_all(dir).ok();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
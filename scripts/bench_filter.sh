@@ -0,0 +1,76 @@
+#!/bin/sh
+# Benchmarks filtering a large app list, one keystroke at a time.
+#
+# Usage: scripts/bench_filter.sh [gyr binary] [number of files] [query]
+#
+# Runs gyr in a detached tmux session with `-vvv`, types `query` one character at a time and
+# then deletes it again, printing the filter time gyr shows next to the query after every
+# keystroke. Typing only scores the previous matches, and deleting restores cached results, so
+# both should stay well under the time of the first keystroke.
+#
+# For matching alone, without the terminal, see `cargo bench --bench filter`.
+#
+# Needs tmux.
+
+set -eu
+
+GYR="${1:-target/release/gyr}"
+FILES="${2:-10000}"
+QUERY="${3:-application 42}"
+
+TMP="$(mktemp -d)"
+SESSION="gyr-bench-filter-$$"
+trap 'tmux kill-session -t "$SESSION" 2> /dev/null; rm -rf "$TMP"' EXIT
+
+# Generate the corpus, with a few different words so fuzzy matching has something to do
+mkdir -p "$TMP/data/applications" "$TMP/home" "$TMP/runtime"
+chmod 700 "$TMP/runtime"
+i=0
+while [ "$i" -lt "$FILES" ]; do
+    case $((i % 4)) in
+        0) kind="Editor" ;;
+        1) kind="Browser" ;;
+        2) kind="Terminal" ;;
+        *) kind="Player" ;;
+    esac
+    cat > "$TMP/data/applications/app$i.desktop" <<EOF
+[Desktop Entry]
+Type=Application
+Name=Application $i $kind
+Comment=Generated application number $i
+Exec=app$i %U
+Terminal=false
+EOF
+    i=$((i + 1))
+done
+
+tmux new-session -d -s "$SESSION" -x 120 -y 30 \
+    -e HOME="$TMP/home" -e XDG_RUNTIME_DIR="$TMP/runtime" -e XDG_DATA_DIRS="$TMP/data" \
+    -e SWAYSOCK= "$GYR -vvv"
+
+# Waits for the list to settle, then prints the last filter time
+filter_time() {
+    sleep 0.3
+    tmux capture-pane -p -t "$SESSION" | sed -n 's/.*) \[\([^]]*\)\] >>.*/\1/p' | tail -n 1
+}
+
+# Wait for gyr to read the corpus
+until tmux capture-pane -p -t "$SESSION" | grep -q "/$FILES) \["; do
+    sleep 0.1
+done
+
+query=""
+rest="$QUERY"
+while [ -n "$rest" ]; do
+    c="$(printf '%s' "$rest" | cut -c 1)"
+    rest="$(printf '%s' "$rest" | cut -c 2-)"
+    query="$query$c"
+    tmux send-keys -t "$SESSION" -l "$c"
+    printf 'type   "%s": %s\n' "$query" "$(filter_time)"
+done
+
+while [ -n "$query" ]; do
+    tmux send-keys -t "$SESSION" BSpace
+    query="$(printf '%s' "$query" | sed 's/.$//')"
+    printf 'delete "%s": %s\n' "$query" "$(filter_time)"
+done
@@ -0,0 +1,65 @@
+#!/bin/sh
+# Benchmarks reading the app index over a generated corpus of desktop files.
+#
+# Usage: scripts/bench_index.sh [gyr binary] [number of files] [runs]
+#
+# Times how long `gyr --daemon` takes to read every desktop file and start listening, with an
+# empty cache. Run it with binaries built from different commits to compare them.
+#
+# For parsing alone, without the daemon, see `cargo bench --bench parse`.
+
+set -eu
+
+GYR="${1:-target/release/gyr}"
+FILES="${2:-5000}"
+RUNS="${3:-5}"
+
+TMP="$(mktemp -d)"
+trap 'rm -rf "$TMP"' EXIT
+
+# Generate the corpus, every app has a few actions
+mkdir -p "$TMP/data/applications"
+i=0
+while [ "$i" -lt "$FILES" ]; do
+    cat > "$TMP/data/applications/app$i.desktop" <<EOF
+[Desktop Entry]
+Type=Application
+Name=Application $i
+Comment=Generated application number $i
+Exec=app$i %U
+Terminal=false
+Categories=Utility;Development;
+Actions=new-window;new-private-window;
+
+[Desktop Action new-window]
+Name=New Window
+Exec=app$i --new-window
+
+[Desktop Action new-private-window]
+Name=New Private Window
+Exec=app$i --private-window
+EOF
+    i=$((i + 1))
+done
+
+run=0
+while [ "$run" -lt "$RUNS" ]; do
+    # Fresh data dir, so the cache is empty
+    rm -rf "$TMP/home" "$TMP/runtime"
+    mkdir -p "$TMP/home" "$TMP/runtime"
+    chmod 700 "$TMP/runtime"
+
+    start=$(date +%s%N)
+    HOME="$TMP/home" XDG_RUNTIME_DIR="$TMP/runtime" XDG_DATA_DIRS="$TMP/data" \
+        "$GYR" --daemon > /dev/null 2>&1 &
+    pid=$!
+    while [ ! -S "$TMP/runtime/gyr.sock" ]; do
+        sleep 0.001
+    done
+    end=$(date +%s%N)
+    kill "$pid"
+    wait "$pid" 2> /dev/null || true
+
+    echo "run $run: $(((end - start) / 1000000)) ms"
+    run=$((run + 1))
+done
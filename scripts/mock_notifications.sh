@@ -0,0 +1,44 @@
+#!/bin/sh
+# Runs a command on a private session bus, with a mock notification server.
+#
+# Usage: scripts/mock_notifications.sh [command...]
+#
+# The mock (`dbus-test-tool echo`) owns org.freedesktop.Notifications and replies to every call,
+# while `dbus-monitor` prints the Notify calls it gets. Try it with `notify = "always"` in the
+# config:
+#
+#     scripts/mock_notifications.sh target/debug/gyr
+#
+# Needs dbus-daemon, dbus-run-session, dbus-test-tool and dbus-monitor from the D-Bus reference
+# implementation.
+
+set -eu
+
+if [ "${MOCK_NOTIFICATIONS_INNER:-}" != 1 ]; then
+    MOCK_NOTIFICATIONS_INNER=1 exec dbus-run-session -- "$0" "$@"
+fi
+
+if [ "$#" -eq 0 ]; then
+    set -- "${SHELL:-sh}"
+fi
+
+LOG="$(mktemp)"
+
+dbus-test-tool echo --name=org.freedesktop.Notifications &
+MOCK=$!
+dbus-monitor "interface='org.freedesktop.Notifications',member='Notify'" > "$LOG" 2>&1 &
+MONITOR=$!
+trap 'kill "$MOCK" "$MONITOR" 2> /dev/null; rm -f "$LOG"' EXIT
+
+# Wait for the mock to own the name
+until dbus-send --session --print-reply --dest=org.freedesktop.DBus /org/freedesktop/DBus \
+    org.freedesktop.DBus.NameHasOwner string:org.freedesktop.Notifications 2> /dev/null |
+    grep -q true; do
+    sleep 0.05
+done
+
+"$@" || true
+
+# Give the monitor a moment to print the last calls
+sleep 0.2
+cat "$LOG"
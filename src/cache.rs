@@ -0,0 +1,193 @@
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path;
+use std::time::SystemTime;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::xdg;
+
+/// Version of the cache format.
+///
+/// Bump it when [DirEntry], [FileEntry] or [App] change after a release, so caches written by
+/// older releases get discarded. Any other version is discarded too, not only older ones
+///
+/// [App]: super::xdg::App
+const VERSION: u64 = 1;
+
+/// Name of the sled tree holding the cache, next to the history
+const TREE: &str = "cache";
+
+/// Key holding the cache format version
+const VERSION_KEY: &[u8] = b"version";
+
+/// A cached directory
+#[derive(Serialize, Deserialize)]
+struct DirEntry {
+    /// Modification time of the directory when it was read
+    mtime: Option<SystemTime>,
+    /// Files in the directory, sorted
+    files: Vec<path::PathBuf>,
+    /// Subdirectories, sorted
+    dirs: Vec<path::PathBuf>,
+}
+
+impl DirEntry {
+    /// Lists `dir`
+    fn read(dir: &path::Path, mtime: Option<SystemTime>) -> Self {
+        let mut entry = Self {
+            mtime,
+            files: vec![],
+            dirs: vec![],
+        };
+
+        if let Ok(read_dir) = fs::read_dir(dir) {
+            for child in read_dir.flatten() {
+                // Like walkdir, don't follow symlinks to directories
+                match child.file_type() {
+                    Ok(kind) if kind.is_dir() => entry.dirs.push(child.path()),
+                    Ok(_) => entry.files.push(child.path()),
+                    Err(_) => (),
+                }
+            }
+        }
+
+        entry.files.sort();
+        entry.dirs.sort();
+        entry
+    }
+}
+
+/// A cached desktop file
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    /// Modification time of the file when it was parsed
+    mtime: SystemTime,
+    /// Size of the file when it was parsed
+    size: u64,
+    /// Apps parsed from the file (without history)
+    apps: Vec<xdg::App>,
+}
+
+/// Cache of parsed desktop files, stored in the history database
+///
+/// Directories are validated by mtime, so unchanged ones aren't listed again.
+/// Files are validated by mtime and size, so only changed ones are parsed again.
+pub struct Cache {
+    tree: sled::Tree,
+}
+
+impl Cache {
+    /// Opens the cache, discarding it if it was written by another version of Gyr
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        let tree = db.open_tree(TREE)?;
+
+        let version = tree
+            .get(VERSION_KEY)?
+            .and_then(|v| v.as_ref().try_into().ok())
+            .map(super::bytes::unpack);
+
+        if version != Some(VERSION) {
+            tree.clear()?;
+            tree.insert(VERSION_KEY, &super::bytes::pack(VERSION))?;
+        }
+
+        Ok(Self { tree })
+    }
+
+    /// Removes everything from the cache
+    pub fn clear(db: &sled::Db) -> sled::Result<()> {
+        db.drop_tree(TREE).map(drop)
+    }
+
+    /// Finds the files in `dir` (recursive)
+    pub fn files(&self, dir: &path::Path) -> Vec<path::PathBuf> {
+        let mut files = vec![];
+        self.walk(dir, &mut files);
+        files
+    }
+
+    /// Adds the files in `dir` and its subdirectories to `files`
+    fn walk(&self, dir: &path::Path, files: &mut Vec<path::PathBuf>) {
+        let key = key(b"dir:", dir);
+        let mtime = fs::metadata(dir).and_then(|m| m.modified()).ok();
+
+        let entry = match self.get::<DirEntry>(&key) {
+            Some(entry) if mtime.is_some() && entry.mtime == mtime => entry,
+            cached => {
+                let entry = DirEntry::read(dir, mtime);
+
+                // Forget entries that were removed
+                if let Some(cached) = cached {
+                    for file in cached.files.iter().filter(|f| !entry.files.contains(f)) {
+                        self.tree.remove(self::key(b"file:", file)).ok();
+                    }
+                    for sub in cached.dirs.iter().filter(|d| !entry.dirs.contains(d)) {
+                        self.tree.remove(self::key(b"dir:", sub)).ok();
+                    }
+                }
+
+                self.set(&key, &entry);
+                entry
+            }
+        };
+
+        files.extend(entry.files);
+        for sub in &entry.dirs {
+            self.walk(sub, files);
+        }
+    }
+
+    /// Gets the apps in `file`, parsing it with `parse` if it changed since it was cached
+    pub fn apps(
+        &self,
+        file: &path::Path,
+        parse: impl FnOnce(&path::Path) -> Vec<xdg::App>,
+    ) -> Vec<xdg::App> {
+        let Ok(metadata) = fs::metadata(file) else {
+            return vec![];
+        };
+        let Ok(mtime) = metadata.modified() else {
+            return parse(file);
+        };
+
+        let key = key(b"file:", file);
+
+        if let Some(entry) = self.get::<FileEntry>(&key) {
+            if entry.mtime == mtime && entry.size == metadata.len() {
+                return entry.apps;
+            }
+        }
+
+        let apps = parse(file);
+        self.set(
+            &key,
+            &FileEntry {
+                mtime,
+                size: metadata.len(),
+                apps: apps.clone(),
+            },
+        );
+        apps
+    }
+
+    /// Reads a value. Undecodable values are treated as missing
+    fn get<T: DeserializeOwned>(&self, key: &[u8]) -> Option<T> {
+        let raw = self.tree.get(key).ok()??;
+        bincode::deserialize(&raw).ok()
+    }
+
+    /// Writes a value. The cache is best-effort, so errors are ignored
+    fn set<T: Serialize>(&self, key: &[u8], value: &T) {
+        if let Ok(raw) = bincode::serialize(value) {
+            self.tree.insert(key, raw).ok();
+        }
+    }
+}
+
+/// Builds a key from a `prefix` and a `path`
+fn key(prefix: &[u8], path: &path::Path) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend_from_slice(path.as_os_str().as_bytes());
+    key
+}
@@ -1,6 +1,11 @@
 use directories::ProjectDirs;
 use serde::Deserialize;
-use std::{env, fs, io, path, process};
+use std::{collections::BTreeMap, env, fs, io, path, process, time};
+
+use super::glyph::{self, Glyphs, GlyphsConf};
+use super::keymap::{Keymap, Mode};
+use super::layout::{Layout, LayoutConf, Size};
+use super::theme::{self, Theme};
 
 fn usage() -> ! {
     println!(
@@ -9,7 +14,11 @@ fn usage() -> ! {
   -s, --nosway           Disable Sway integration.
   -c, --config <config>  Specify a config file.
   -r, --replace          Replace existing gyr instances
+  -t, --toggle           Close the running gyr instance, or start if there's none.
+  -d, --daemon           Keep the app index in memory, serving it to other gyr instances.
+  --height <height>      Draw below the cursor, in this many lines (or N% of the terminal).
   --clear_history        Clear launch history.
+  --rebuild-cache        Parse all desktop files again, discarding the cache.
   -v, --verbose          Increase verbosity level (multiple).
   -h, --help             Show this help message.
   -V, --version          Show the version number and quit.
@@ -22,35 +31,113 @@ fn usage() -> ! {
 /// Command line interface.
 #[derive(Debug)]
 pub struct Opts {
-    /// Highlight color used in the UI
-    pub highlight_color: ratatui::style::Color,
+    /// Styles of the UI elements
+    pub theme: Theme,
+    /// Where each pane goes
+    pub layout: Layout,
+    /// Lines to draw in, below the cursor. Fullscreen if `None`
+    pub height: Option<Size>,
+    /// Draw on the alternate screen, leaving the terminal as it was
+    pub alternate_screen: bool,
+    /// How the icon of the selected app is shown
+    pub icons: Icons,
+    /// Icon theme to look for icons in
+    pub icon_theme: String,
+    /// Glyphs shown before app names
+    pub glyphs: Glyphs,
     /// Clear the history database
     pub clear_history: bool,
     /// Command to run Terminal=true apps
     pub terminal_launcher: String,
     /// Replace already running instance of Gyr
     pub replace: bool,
+    /// Close the running instance of Gyr instead of starting
+    pub toggle: bool,
     /// Enable Sway integration (default when `$SWAYSOCK` is not empty)
     pub sway: bool,
-    /// Cursor character for the search
-    pub cursor: String,
     /// Verbosity level
     pub verbose: Option<u64>,
     /// Don't scroll past the last/first item
     pub hard_stop: bool,
+    /// Run as a daemon instead of showing the UI
+    pub daemon: bool,
+    /// Discard the desktop entry cache
+    pub rebuild_cache: bool,
+    /// Watch the application directories while running
+    pub live_reload: bool,
+    /// Wait for enter after printing a fatal error
+    pub error_prompt: bool,
+    /// How long to wait for launched apps to fail (zero disables it)
+    pub launch_grace_period: time::Duration,
+    /// Which launches to send desktop notifications for
+    pub notify: Notify,
+    /// Key bindings
+    pub keymap: Keymap,
+    /// Apps to keep visible above and below the selection
+    pub scrolloff: usize,
+    /// Keep the selected app selected while it matches the query
+    pub keep_selection: bool,
+    /// List desktop actions matching the query next to the apps
+    pub search_actions: bool,
+}
+
+/// How the icon of the selected app is shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Icons {
+    /// Not at all
+    #[default]
+    None,
+    /// With a graphics protocol if the terminal supports one, as text otherwise
+    Auto,
+    /// With the kitty graphics protocol
+    Kitty,
+    /// As sixel graphics
+    Sixel,
+    /// As the initial of the app, in a box
+    Text,
+}
+
+/// Which launches to send desktop notifications for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notify {
+    /// Don't send notifications
+    #[default]
+    Never,
+    /// Only when an app fails to launch
+    Failures,
+    /// When an app fails to launch, and when it's launched
+    Always,
 }
 
 impl Default for Opts {
     fn default() -> Self {
         Self {
-            highlight_color: ratatui::style::Color::LightBlue,
+            theme: Theme::default(),
+            layout: Layout::default(),
+            height: None,
+            alternate_screen: false,
+            icons: Icons::None,
+            icon_theme: "hicolor".to_string(),
+            glyphs: Glyphs::default(),
             clear_history: false,
             terminal_launcher: "alacritty -e".to_string(),
             replace: false,
+            toggle: false,
             sway: false,
-            cursor: "█".to_string(),
             verbose: None,
             hard_stop: false,
+            daemon: false,
+            rebuild_cache: false,
+            live_reload: true,
+            error_prompt: true,
+            launch_grace_period: time::Duration::ZERO,
+            notify: Notify::Never,
+            keymap: Keymap::new(false),
+            scrolloff: 0,
+            keep_selection: false,
+            search_actions: true,
         }
     }
 }
@@ -74,12 +161,24 @@ pub fn parse() -> Result<Opts, lexopt::Error> {
             Short('r') | Long("replace") => {
                 default.replace = true;
             }
+            Short('t') | Long("toggle") => {
+                default.toggle = true;
+            }
             Short('c') | Long("config") => {
                 config_file = Some(path::PathBuf::from(parser.value()?));
             }
+            Short('d') | Long("daemon") => {
+                default.daemon = true;
+            }
+            Long("height") => {
+                default.height = Some(parser.value()?.parse()?);
+            }
             Long("clear_history") => {
                 default.clear_history = true;
             }
+            Long("rebuild-cache") => {
+                default.rebuild_cache = true;
+            }
             Short('v') | Long("verbose") => {
                 if let Some(v) = default.verbose {
                     default.verbose = Some(v + 1);
@@ -114,6 +213,16 @@ pub fn parse() -> Result<Opts, lexopt::Error> {
             match fs::read_to_string(&f) {
                 Ok(content) => match FileConf::read(&content) {
                     Ok(conf) => {
+                        for key in conf.unknown.keys() {
+                            let reason = match key.as_str() {
+                                "cursor" => "was removed, the query shows the terminal cursor",
+                                _ => "is unknown",
+                            };
+                            eprintln!(
+                                "Warning: ignoring `{key}` in config file {}, it {reason}",
+                                f.display()
+                            );
+                        }
                         file_conf = Some(conf);
                     }
                     Err(e) => {
@@ -137,29 +246,108 @@ pub fn parse() -> Result<Opts, lexopt::Error> {
 
     let file_conf = file_conf.unwrap_or_default();
 
-    if let Some(color) = file_conf.highlight_color {
-        match string_to_color(color) {
-            Ok(color) => default.highlight_color = color,
+    if let Some(name) = file_conf.theme {
+        match Theme::named(&name) {
+            Ok(theme) => default.theme = theme,
             Err(e) => {
-                // @TODO: Better error messages
                 eprintln!("Error parsing config file: {e}");
                 std::process::exit(1);
             }
         }
     }
 
-    if let Some(command) = file_conf.terminal_launcher {
-        default.terminal_launcher = command;
+    if let Some(color) = file_conf.highlight_color {
+        match theme::parse_color(&color) {
+            Ok(color) => default.theme.highlight(color),
+            Err(e) => {
+                eprintln!("Error parsing config file: highlight_color: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    for (element, style) in file_conf.styles.unwrap_or_default() {
+        if let Err(e) = default.theme.set(&element, &style) {
+            eprintln!("Error parsing config file: [styles] {e}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(layout) = file_conf.layout {
+        default.layout.configure(layout);
+    }
+
+    // The command line wins
+    if default.height.is_none() {
+        default.height = file_conf.height;
+    }
+
+    if let Some(a) = file_conf.alternate_screen {
+        default.alternate_screen = a;
+    }
+
+    if let Some(i) = file_conf.icons {
+        default.icons = i;
+    }
+
+    if let Some(theme) = file_conf.icon_theme {
+        default.icon_theme = theme;
+    }
+
+    default.glyphs = Glyphs::new(file_conf.glyphs.unwrap_or_default());
+    if let Some(glyphs) = file_conf.glyph_map {
+        default.glyphs.configure(glyphs);
     }
 
-    if let Some(c) = file_conf.cursor {
-        default.cursor = c;
+    if let Some(command) = file_conf.terminal_launcher {
+        default.terminal_launcher = command;
     }
 
     if let Some(h) = file_conf.hard_stop {
         default.hard_stop = h;
     }
 
+    if let Some(l) = file_conf.live_reload {
+        default.live_reload = l;
+    }
+
+    if let Some(p) = file_conf.error_prompt {
+        default.error_prompt = p;
+    }
+
+    if let Some(ms) = file_conf.launch_grace_period {
+        default.launch_grace_period = time::Duration::from_millis(ms);
+    }
+
+    if let Some(n) = file_conf.notify {
+        default.notify = n;
+    }
+
+    if let Some(s) = file_conf.scrolloff {
+        default.scrolloff = s;
+    }
+
+    if let Some(k) = file_conf.keep_selection {
+        default.keep_selection = k;
+    }
+
+    if let Some(s) = file_conf.search_actions {
+        default.search_actions = s;
+    }
+
+    default.keymap = Keymap::new(file_conf.vi_mode.unwrap_or(false));
+    for (mode, keys) in [
+        (Mode::Insert, file_conf.keys),
+        (Mode::Normal, file_conf.normal_keys),
+    ] {
+        for (chord, action) in keys.unwrap_or_default() {
+            if let Err(e) = default.keymap.bind(mode, &chord, &action) {
+                eprintln!("Error parsing config file: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     Ok(default)
 }
 
@@ -170,12 +358,51 @@ pub fn parse() -> Result<Opts, lexopt::Error> {
 pub struct FileConf {
     /// Highlight color used in the UI
     pub highlight_color: Option<String>,
+    /// Bundled theme to start from
+    pub theme: Option<String>,
+    /// Styles of the UI elements, element name to style
+    pub styles: Option<BTreeMap<String, theme::StyleConf>>,
+    /// Where each pane goes
+    pub layout: Option<LayoutConf>,
+    /// Lines to draw in, below the cursor, instead of fullscreen
+    pub height: Option<Size>,
+    /// Draw on the alternate screen, leaving the terminal as it was
+    pub alternate_screen: Option<bool>,
+    /// How the icon of the selected app is shown
+    pub icons: Option<Icons>,
+    /// Icon theme to look for icons in
+    pub icon_theme: Option<String>,
+    /// Bundled glyphs shown before app names
+    pub glyphs: Option<glyph::Set>,
+    /// Glyphs shown before app names, replacing the bundled ones
+    pub glyph_map: Option<GlyphsConf>,
     /// Command to run Terminal=true apps
     pub terminal_launcher: Option<String>,
-    /// Cursor character for the search
-    pub cursor: Option<String>,
     /// Don't scroll past the last/first item
     pub hard_stop: Option<bool>,
+    /// Watch the application directories while running
+    pub live_reload: Option<bool>,
+    /// Wait for enter after printing a fatal error
+    pub error_prompt: Option<bool>,
+    /// How long to wait for launched apps to fail, in milliseconds (0 disables it)
+    pub launch_grace_period: Option<u64>,
+    /// Which launches to send desktop notifications for
+    pub notify: Option<Notify>,
+    /// Enable vi-like normal and insert modes
+    pub vi_mode: Option<bool>,
+    /// Key bindings (insert mode with `vi_mode`), key chord to action name
+    pub keys: Option<BTreeMap<String, String>>,
+    /// Key bindings in vi normal mode, key chord to action name
+    pub normal_keys: Option<BTreeMap<String, String>>,
+    /// Apps to keep visible above and below the selection
+    pub scrolloff: Option<usize>,
+    /// Keep the selected app selected while it matches the query
+    pub keep_selection: Option<bool>,
+    /// List desktop actions matching the query next to the apps
+    pub search_actions: Option<bool>,
+    /// Options Gyr doesn't know, or doesn't use anymore. Ignored with a warning
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, toml::Value>,
 }
 
 impl FileConf {
@@ -185,30 +412,22 @@ impl FileConf {
     }
 }
 
-/// Parses a [String] into a ratatui [color]
-///
-/// Case-insensitive
-///
-/// [String]: std::string::String
-/// [color]: tui::style::Color
-fn string_to_color<T: Into<String>>(val: T) -> Result<ratatui::style::Color, &'static str> {
-    match val.into().to_lowercase().as_ref() {
-        "black" => Ok(ratatui::style::Color::Black),
-        "red" => Ok(ratatui::style::Color::Red),
-        "green" => Ok(ratatui::style::Color::Green),
-        "yellow" => Ok(ratatui::style::Color::Yellow),
-        "blue" => Ok(ratatui::style::Color::Blue),
-        "magenta" => Ok(ratatui::style::Color::Magenta),
-        "cyan" => Ok(ratatui::style::Color::Cyan),
-        "gray" => Ok(ratatui::style::Color::Gray),
-        "darkgray" => Ok(ratatui::style::Color::DarkGray),
-        "lightred" => Ok(ratatui::style::Color::LightRed),
-        "lightgreen" => Ok(ratatui::style::Color::LightGreen),
-        "lightyellow" => Ok(ratatui::style::Color::LightYellow),
-        "lightblue" => Ok(ratatui::style::Color::LightBlue),
-        "lightmagenta" => Ok(ratatui::style::Color::LightMagenta),
-        "lightcyan" => Ok(ratatui::style::Color::LightCyan),
-        "white" => Ok(ratatui::style::Color::White),
-        _ => Err("unknow color"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys() {
+        let conf = FileConf::read("hard_stop = true\ncursor = \"|\"\n[bogus]\nkey = 1\n").unwrap();
+        assert_eq!(conf.hard_stop, Some(true));
+        assert_eq!(conf.unknown.keys().collect::<Vec<_>>(), ["bogus", "cursor"]);
+    }
+
+    #[test]
+    fn known_keys() {
+        let conf = FileConf::read(include_str!("../config.toml")).unwrap();
+        assert!(conf.unknown.is_empty(), "{:?}", conf.unknown.keys());
+        // Still type checked
+        assert!(FileConf::read("hard_stop = \"yes\"").is_err());
     }
 }
@@ -0,0 +1,102 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path;
+use std::thread;
+use std::time::Duration;
+
+use eyre::{eyre, WrapErr};
+
+use super::signal;
+
+/// How long to wait for the running instance to quit
+const QUIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Path of the control socket, `$XDG_RUNTIME_DIR/gyr-control.sock`
+pub fn socket_path() -> Option<path::PathBuf> {
+    let mut path = dirs::runtime_dir()?;
+    path.push(concat!(env!("CARGO_PKG_NAME"), "-control.sock"));
+    Some(path)
+}
+
+/// Control socket of the running instance
+///
+/// Other instances can ask it to quit through it. The socket is removed when dropped.
+pub struct Server {
+    path: path::PathBuf,
+}
+
+impl Server {
+    /// Starts listening in a background thread.
+    ///
+    /// Must be called while holding the [Lock], so any existing socket is stale
+    ///
+    /// [Lock]: super::lock::Lock
+    pub fn start() -> eyre::Result<Self> {
+        let path = socket_path().ok_or_else(|| eyre!("$XDG_RUNTIME_DIR is not set"))?;
+
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e).wrap_err("Failed to remove stale control socket");
+            }
+            _ => (),
+        }
+
+        let listener = UnixListener::bind(&path)
+            .wrap_err_with(|| format!("Failed to bind to {}", path.display()))?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || handle(stream));
+            }
+        });
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Handles a single command
+fn handle(stream: UnixStream) {
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return;
+    }
+
+    if line.trim() == "quit" {
+        signal::request_quit();
+        // Keep the connection open: it's closed when we exit, which the client waits for
+        loop {
+            thread::park();
+        }
+    }
+}
+
+/// Asks the running instance to quit, waiting until it exits.
+///
+/// Returns `false` if there's no running instance.
+pub fn quit_running() -> eyre::Result<bool> {
+    let path = socket_path().ok_or_else(|| eyre!("$XDG_RUNTIME_DIR is not set"))?;
+    let Ok(mut stream) = UnixStream::connect(path) else {
+        return Ok(false);
+    };
+
+    stream
+        .write_all(b"quit\n")
+        .wrap_err("Failed to send quit command")?;
+
+    // The connection is closed once the instance exits
+    stream.set_read_timeout(Some(QUIT_TIMEOUT))?;
+    match stream.read(&mut [0]) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+            Err(eyre!("The running Gyr instance didn't quit"))
+        }
+        Err(e) => Err(e).wrap_err("Failed to wait for the running instance to quit"),
+    }
+}
@@ -0,0 +1,316 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use super::cache::Cache;
+use super::signal;
+use super::xdg;
+
+/// How long a [Client] waits for the daemon, before reading the apps itself
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often the daemon checks whether it was asked to quit, while waiting for clients
+const QUIT_CHECK: Duration = Duration::from_millis(500);
+
+/// Request sent by a [Client] to the daemon
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    /// Send the whole app index
+    List,
+    /// An app was launched, bump its history. Apps are told apart by desktop file ID and action
+    Launched(String, Option<String>),
+    /// An app failed to start, bump its failures
+    Failed(String, Option<String>),
+    /// Clear the history database
+    ClearHistory,
+    /// Discard the desktop entry cache and reload the index
+    RebuildCache,
+}
+
+/// Reply sent by the daemon to a [Client]
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    /// The app index
+    Apps(Vec<xdg::App>),
+    /// Request handled succesfully
+    Ok,
+    /// Request failed
+    Error(String),
+}
+
+/// Path of the daemon socket, `$XDG_RUNTIME_DIR/gyr.sock`
+pub fn socket_path() -> Option<path::PathBuf> {
+    let mut path = dirs::runtime_dir()?;
+    path.push(concat!(env!("CARGO_PKG_NAME"), ".sock"));
+    Some(path)
+}
+
+/// Runs the daemon, until it's killed or there's some error. The socket is removed on the way out.
+///
+/// Keeps the app index and history in memory, updating the index when files in `dirs` change
+/// (Linux only). The history database at `hist_db` is opened here, once we know no other daemon
+/// holds it.
+pub fn run(dirs: Vec<path::PathBuf>, hist_db: path::PathBuf) -> eyre::Result<()> {
+    let socket = socket_path().ok_or_else(|| eyre!("$XDG_RUNTIME_DIR is not set"))?;
+
+    if socket.exists() {
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(eyre!("Gyr daemon is already running"));
+        }
+        // Left behind by a daemon that didn't exit cleanly
+        fs::remove_file(&socket).wrap_err("Failed to remove stale socket")?;
+    }
+
+    signal::install().wrap_err("Failed to install signal handlers")?;
+    let db = sled::open(hist_db).wrap_err("Failed to open database")?;
+
+    // Changes made while loading come after the apps, so none are missed
+    let events = xdg::read(dirs.clone(), &db, true, true);
+    let index = Arc::new(RwLock::new(apps(&events)));
+
+    // Update the index in the background
+    {
+        let index = Arc::clone(&index);
+        thread::spawn(move || watch(&events, &index));
+    }
+
+    let listener = UnixListener::bind(&socket)
+        .wrap_err_with(|| format!("Failed to bind to {}", socket.display()))?;
+    let result = listen(&listener, &dirs, &index, &db);
+    fs::remove_file(&socket).ok();
+    result
+}
+
+/// Serves the clients connecting to `listener`, each in its own thread, until a termination
+/// signal is received
+fn listen(
+    listener: &UnixListener,
+    dirs: &[path::PathBuf],
+    index: &Arc<RwLock<Vec<xdg::App>>>,
+    db: &sled::Db,
+) -> eyre::Result<()> {
+    while !signal::received() {
+        // Signals don't interrupt accept(), they're restarted
+        let mut pollfd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Safety: poll only writes to `revents`
+        #[allow(unsafe_code)]
+        let ready = unsafe { libc::poll(&mut pollfd, 1, QUIT_CHECK.as_millis() as libc::c_int) };
+        if ready < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error).wrap_err("Failed to wait for clients");
+            }
+        }
+        if ready <= 0 {
+            continue;
+        }
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let index = Arc::clone(index);
+                let db = db.clone();
+                let dirs = dirs.to_vec();
+                thread::spawn(move || {
+                    if let Err(error) = serve(stream, &dirs, &index, &db) {
+                        eprintln!("{error:?}");
+                    }
+                });
+            }
+            Err(error) => eprintln!("Failed to accept connection: {error}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads all the apps in `dirs`
+fn load(dirs: &[path::PathBuf], db: &sled::Db) -> Vec<xdg::App> {
+    apps(&xdg::read(dirs.to_vec(), db, false, false))
+}
+
+/// Takes the apps sent by [xdg::read], until they're all loaded
+///
+/// [xdg::read]: super::xdg::read
+fn apps(events: &mpsc::Receiver<xdg::Event>) -> Vec<xdg::App> {
+    events
+        .iter()
+        .map_while(|event| match event {
+            xdg::Event::Add(app) => Some(app),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Applies the changes sent by [xdg::read] to `index`, like live reload does in the UI
+///
+/// [xdg::read]: super::xdg::read
+fn watch(events: &mpsc::Receiver<xdg::Event>, index: &RwLock<Vec<xdg::App>>) {
+    for event in events {
+        let (path, apps) = match event {
+            xdg::Event::Update(path, apps) => (path, apps),
+            xdg::Event::Remove(path) => (path, vec![]),
+            _ => continue,
+        };
+        let mut index = index.write().unwrap();
+        index.retain(|app| !app.file.starts_with(&path));
+        index.extend(apps);
+    }
+}
+
+/// Handles requests from a single client, until it disconnects
+fn serve(
+    stream: UnixStream,
+    dirs: &[path::PathBuf],
+    index: &RwLock<Vec<xdg::App>>,
+    db: &sled::Db,
+) -> eyre::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+
+    let history = xdg::AppHistory::new(db)?;
+
+    // Deserializing fails when the client closes the connection
+    while let Ok(request) = bincode::deserialize_from::<_, Request>(&mut reader) {
+        let response = match request {
+            Request::List => Response::Apps(index.read().unwrap().clone()),
+            Request::Launched(id, action) => update(index, &id, action.as_deref(), |app| {
+                app.history = history.launched(app)?;
+                Ok(())
+            }),
+            Request::Failed(id, action) => update(index, &id, action.as_deref(), |app| {
+                app.failures = history.failed(app)?;
+                Ok(())
+            }),
+            Request::ClearHistory => match history.clear() {
+                Ok(()) => {
+                    for app in index.write().unwrap().iter_mut() {
+                        app.history = 0;
+                        app.failures = 0;
+                    }
+                    Response::Ok
+                }
+                Err(error) => Response::Error(error.to_string()),
+            },
+            Request::RebuildCache => match Cache::clear(db) {
+                Ok(()) => {
+                    let apps = load(dirs, db);
+                    *index.write().unwrap() = apps;
+                    Response::Ok
+                }
+                Err(error) => Response::Error(error.to_string()),
+            },
+        };
+
+        bincode::serialize_into(&mut writer, &response)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Runs `f` on the apps in `index` with the desktop file ID `id`, and `action`. There can be more
+/// than one, if the same desktop file ID is found in several directories
+fn update(
+    index: &RwLock<Vec<xdg::App>>,
+    id: &str,
+    action: Option<&str>,
+    mut f: impl FnMut(&mut xdg::App) -> sled::Result<()>,
+) -> Response {
+    let mut index = index.write().unwrap();
+    let matches = |app: &xdg::App| app.action.as_deref() == action && app.id() == id;
+    for app in index.iter_mut().filter(|app| matches(app)) {
+        if let Err(error) = f(app) {
+            return Response::Error(error.to_string());
+        }
+    }
+    Response::Ok
+}
+
+/// Connection to a running daemon
+pub struct Client {
+    /// Buffered for reading, the replies can be big. Requests are written to the socket directly
+    stream: BufReader<UnixStream>,
+}
+
+impl Client {
+    /// Connects to the daemon, if it's running
+    ///
+    /// Requests fail if the daemon takes longer than [TIMEOUT] to answer.
+    pub fn connect() -> Option<Self> {
+        let stream = UnixStream::connect(socket_path()?).ok()?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+        stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+        Some(Self {
+            stream: BufReader::new(stream),
+        })
+    }
+
+    /// Sends `request`, waiting for the reply
+    fn request(&mut self, request: &Request) -> eyre::Result<Response> {
+        bincode::serialize_into(self.stream.get_ref(), request)
+            .wrap_err("Failed to send request to daemon")?;
+        let response = bincode::deserialize_from(&mut self.stream)
+            .wrap_err("Failed to read reply from daemon")?;
+
+        match response {
+            Response::Error(error) => Err(eyre!("Daemon error: {error}")),
+            response => Ok(response),
+        }
+    }
+
+    /// Gets the app index from the daemon
+    ///
+    /// Sends the apps via a mpsc [Receiver], like [xdg::read] (without watching)
+    ///
+    /// [Receiver]: std::sync::mpsc::Receiver
+    /// [xdg::read]: super::xdg::read
+    pub fn read(&mut self) -> eyre::Result<mpsc::Receiver<xdg::Event>> {
+        let (sender, receiver) = mpsc::channel();
+
+        match self.request(&Request::List)? {
+            Response::Apps(apps) => {
+                for app in apps {
+                    sender.send(xdg::Event::Add(app))?;
+                }
+                sender.send(xdg::Event::Loaded)?;
+            }
+            response => return Err(eyre!("Unexpected reply from daemon: {response:?}")),
+        }
+
+        Ok(receiver)
+    }
+
+    /// Tells the daemon that `app` was launched
+    pub fn launched(&mut self, app: &xdg::App) -> eyre::Result<()> {
+        self.request(&Request::Launched(app.id(), app.action.clone()))
+            .map(drop)
+    }
+
+    /// Tells the daemon that `app` failed to start
+    pub fn failed(&mut self, app: &xdg::App) -> eyre::Result<()> {
+        self.request(&Request::Failed(app.id(), app.action.clone()))
+            .map(drop)
+    }
+
+    /// Clears the daemon's history database
+    pub fn clear_history(&mut self) -> eyre::Result<()> {
+        self.request(&Request::ClearHistory).map(drop)
+    }
+
+    /// Makes the daemon discard its desktop entry cache and reload the index
+    pub fn rebuild_cache(&mut self) -> eyre::Result<()> {
+        self.request(&Request::RebuildCache).map(drop)
+    }
+}
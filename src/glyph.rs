@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+use unicode_width::UnicodeWidthStr;
+
+use super::xdg::App;
+
+/// Nerd Font glyphs for main and common additional categories
+const NERD_CATEGORIES: &[(&str, &str)] = &[
+    ("AudioVideo", "\u{f008}"),
+    ("Audio", "\u{f001}"),
+    ("Video", "\u{f03d}"),
+    ("Development", "\u{f121}"),
+    ("Education", "\u{f02d}"),
+    ("Game", "\u{f11b}"),
+    ("Graphics", "\u{f1fc}"),
+    ("Network", "\u{f0ac}"),
+    ("Office", "\u{f0b1}"),
+    ("Science", "\u{f0c3}"),
+    ("Settings", "\u{f013}"),
+    ("System", "\u{f108}"),
+    ("Utility", "\u{f0ad}"),
+    ("WebBrowser", "\u{f0ac}"),
+    ("Email", "\u{f0e0}"),
+    ("Chat", "\u{f086}"),
+    ("InstantMessaging", "\u{f086}"),
+    ("TerminalEmulator", "\u{f120}"),
+    ("FileManager", "\u{f07b}"),
+    ("TextEditor", "\u{f040}"),
+    ("Calculator", "\u{f1ec}"),
+    ("Viewer", "\u{f03e}"),
+];
+
+/// Nerd Font glyphs for well-known icon names
+const NERD_ICONS: &[(&str, &str)] = &[
+    ("firefox", "\u{f269}"),
+    ("chromium", "\u{f268}"),
+    ("google-chrome", "\u{f268}"),
+    ("steam", "\u{f1b6}"),
+    ("spotify", "\u{f1bc}"),
+    ("telegram", "\u{f2c6}"),
+    ("org.telegram.desktop", "\u{f2c6}"),
+    ("slack", "\u{f198}"),
+    ("gimp", "\u{f338}"),
+    ("org.gimp.GIMP", "\u{f338}"),
+    ("inkscape", "\u{f33b}"),
+    ("org.inkscape.Inkscape", "\u{f33b}"),
+    ("gvim", "\u{e62b}"),
+    ("nvim", "\u{e62b}"),
+    ("utilities-terminal", "\u{f120}"),
+];
+
+/// Nerd Font glyph for apps without one
+const NERD_DEFAULT: &str = "\u{f135}";
+
+/// Emoji for main and common additional categories
+const EMOJI_CATEGORIES: &[(&str, &str)] = &[
+    ("AudioVideo", "🎬"),
+    ("Audio", "🎵"),
+    ("Video", "📺"),
+    ("Development", "🔨"),
+    ("Education", "🎓"),
+    ("Game", "🎮"),
+    ("Graphics", "🎨"),
+    ("Network", "🌐"),
+    ("Office", "📄"),
+    ("Science", "🔬"),
+    ("Settings", "🔧"),
+    ("System", "💾"),
+    ("Utility", "🧰"),
+    ("WebBrowser", "🌐"),
+    ("Email", "📧"),
+    ("Chat", "💬"),
+    ("InstantMessaging", "💬"),
+    ("TerminalEmulator", "💻"),
+    ("FileManager", "📁"),
+    ("TextEditor", "📝"),
+    ("Calculator", "🧮"),
+    ("Viewer", "📷"),
+];
+
+/// Emoji for apps without one
+const EMOJI_DEFAULT: &str = "🚀";
+
+/// Bundled glyphs to start from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Set {
+    /// None, only the ones in the config file
+    #[default]
+    None,
+    /// Nerd Font icons
+    Nerd,
+    /// Emoji
+    Emoji,
+}
+
+/// Glyphs, as written in the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GlyphsConf {
+    /// By desktop file ID
+    pub ids: Option<BTreeMap<String, String>>,
+    /// By `StartupWMClass`
+    pub classes: Option<BTreeMap<String, String>>,
+    /// By `Icon` name
+    pub icons: Option<BTreeMap<String, String>>,
+    /// By category
+    pub categories: Option<BTreeMap<String, String>>,
+    /// For apps without one
+    pub default: Option<String>,
+}
+
+/// Glyph shown before each app name, looked up by desktop file ID, then `StartupWMClass`, then
+/// `Icon` name and then by category
+///
+/// Glyphs are padded to the widest one, so names stay aligned. An empty glyph shows nothing, and
+/// stops the lookup.
+#[derive(Debug, Clone, Default)]
+pub struct Glyphs {
+    /// By desktop file ID
+    ids: HashMap<String, String>,
+    /// By lowercase `StartupWMClass`
+    classes: HashMap<String, String>,
+    /// By `Icon` name
+    icons: HashMap<String, String>,
+    /// By category
+    categories: HashMap<String, String>,
+    /// For apps without one
+    default: Option<String>,
+    /// Columns taken by the widest glyph
+    width: usize,
+}
+
+impl Glyphs {
+    /// Glyphs bundled in `set`
+    pub fn new(set: Set) -> Self {
+        let table = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, glyph)| (key.to_string(), glyph.to_string()))
+                .collect()
+        };
+        let mut glyphs = match set {
+            Set::None => Self::default(),
+            Set::Nerd => Self {
+                icons: table(NERD_ICONS),
+                categories: table(NERD_CATEGORIES),
+                default: Some(NERD_DEFAULT.to_string()),
+                ..Self::default()
+            },
+            Set::Emoji => Self {
+                categories: table(EMOJI_CATEGORIES),
+                default: Some(EMOJI_DEFAULT.to_string()),
+                ..Self::default()
+            },
+        };
+        glyphs.measure();
+        glyphs
+    }
+
+    /// Adds the glyphs in `conf`, replacing the bundled ones
+    pub fn configure(&mut self, conf: GlyphsConf) {
+        self.ids.extend(conf.ids.unwrap_or_default());
+        self.classes.extend(
+            conf.classes
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(class, glyph)| (class.to_lowercase(), glyph)),
+        );
+        self.icons.extend(conf.icons.unwrap_or_default());
+        self.categories.extend(conf.categories.unwrap_or_default());
+        if let Some(glyph) = conf.default {
+            self.default = Some(glyph);
+        }
+        self.measure();
+    }
+
+    /// Glyph of `app`, padded to the widest glyph and followed by a space. `None` if there are no
+    /// glyphs at all
+    pub fn cell(&self, app: &App) -> Option<String> {
+        if self.width == 0 {
+            return None;
+        }
+        let glyph = self.get(app).unwrap_or_default();
+        let padding = self.width.saturating_sub(glyph.width());
+        Some(format!("{glyph}{} ", " ".repeat(padding)))
+    }
+
+    /// Glyph of `app`, if it has one
+    fn get(&self, app: &App) -> Option<&str> {
+        let class = app.startup_wm_class.as_ref().map(|c| c.to_lowercase());
+        self.ids
+            .get(&app.id())
+            .or_else(|| self.classes.get(class.as_ref()?))
+            .or_else(|| self.icons.get(app.icon.as_ref()?))
+            // The most specific category, they come after the main one
+            .or_else(|| {
+                app.categories
+                    .iter()
+                    .rev()
+                    .find_map(|category| self.categories.get(category))
+            })
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Updates the width of the widest glyph
+    fn measure(&mut self) {
+        self.width = [&self.ids, &self.classes, &self.icons, &self.categories]
+            .into_iter()
+            .flat_map(HashMap::values)
+            .chain(&self.default)
+            .map(|glyph| glyph.width())
+            .max()
+            .unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// App read from `applications/org.example.Editor.desktop`
+    fn app() -> App {
+        let mut app = App::parse(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=Editor\n\
+             Exec=editor\n\
+             Icon=accessories-text-editor\n\
+             StartupWMClass=Editor\n\
+             Categories=Utility;TextEditor;\n",
+            None,
+        )
+        .unwrap();
+        app.file = "/usr/share/applications/org.example.Editor.desktop".into();
+        app
+    }
+
+    /// Glyphs with one entry in each table, each one different
+    fn glyphs() -> Glyphs {
+        let map = |key: &str, glyph: &str| Some(BTreeMap::from([(key.into(), glyph.into())]));
+        let mut glyphs = Glyphs::new(Set::None);
+        glyphs.configure(GlyphsConf {
+            ids: map("org.example.Editor", "i"),
+            classes: map("EDITOR", "c"),
+            icons: map("accessories-text-editor", "n"),
+            categories: map("TextEditor", "t"),
+            default: Some("d".to_string()),
+        });
+        glyphs
+    }
+
+    #[test]
+    fn lookup_order() {
+        let mut app = app();
+        let mut glyphs = glyphs();
+        assert_eq!(glyphs.get(&app), Some("i"));
+        glyphs.ids.clear();
+        // Classes are case-insensitive
+        assert_eq!(glyphs.get(&app), Some("c"));
+        glyphs.classes.clear();
+        assert_eq!(glyphs.get(&app), Some("n"));
+        glyphs.icons.clear();
+        assert_eq!(glyphs.get(&app), Some("t"));
+        // The most specific category wins
+        glyphs.categories.insert("Utility".into(), "u".into());
+        assert_eq!(glyphs.get(&app), Some("t"));
+        app.categories = vec!["TextEditor".into(), "Utility".into()];
+        assert_eq!(glyphs.get(&app), Some("u"));
+        glyphs.categories.clear();
+        assert_eq!(glyphs.get(&app), Some("d"));
+        glyphs.default = None;
+        assert_eq!(glyphs.get(&app), None);
+    }
+
+    #[test]
+    fn empty_stops_lookup() {
+        let mut glyphs = glyphs();
+        glyphs
+            .ids
+            .insert("org.example.Editor".into(), String::new());
+        glyphs.measure();
+        assert_eq!(glyphs.get(&app()), Some(""));
+        assert_eq!(glyphs.cell(&app()).as_deref(), Some("  "));
+    }
+
+    #[test]
+    fn cell_padding() {
+        assert_eq!(Glyphs::new(Set::None).cell(&app()), None);
+
+        let mut glyphs = glyphs();
+        assert_eq!(glyphs.cell(&app()).as_deref(), Some("i "));
+        // Padded to the widest glyph, in columns
+        glyphs.configure(GlyphsConf {
+            default: Some("🚀".to_string()),
+            ..GlyphsConf::default()
+        });
+        assert_eq!(glyphs.cell(&app()).as_deref(), Some("i  "));
+    }
+
+    #[test]
+    fn bundled() {
+        let nerd = Glyphs::new(Set::Nerd);
+        assert_eq!(nerd.get(&app()), Some("\u{f040}"));
+        let emoji = Glyphs::new(Set::Emoji);
+        assert_eq!(emoji.get(&app()), Some("📝"));
+
+        // The config file replaces bundled glyphs
+        let mut emoji = emoji;
+        emoji.configure(GlyphsConf {
+            categories: Some(BTreeMap::from([("TextEditor".into(), "✎".into())])),
+            ..GlyphsConf::default()
+        });
+        assert_eq!(emoji.get(&app()), Some("✎"));
+    }
+}
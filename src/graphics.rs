@@ -0,0 +1,582 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use ratatui::layout::Rect;
+use serde::Deserialize;
+
+/// Asks whether kitty graphics work (`a=q` only queries, nothing is drawn), then for the primary
+/// device attributes, which every terminal answers, listing sixel support
+pub const QUERY: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c";
+
+/// How long to wait for the terminal to answer [QUERY]
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait for the rest of an answer that started, so none of it is read as keys
+const DRAIN: Duration = Duration::from_secs(2);
+
+/// Size of a cell in pixels, when the terminal doesn't tell
+const CELL: (u16, u16) = (8, 16);
+
+/// Bytes of base64 sent in each kitty escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+/// Every PNG file starts with this
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Biggest width or height decoded, icons are much smaller
+const MAX_SIZE: u32 = 4096;
+
+/// Memory the PNG decoder can take, enough for [MAX_SIZE] square in RGBA, twice
+const MAX_BYTES: usize = 2 * 4 * MAX_SIZE as usize * MAX_SIZE as usize;
+
+/// Ways to draw images in a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Kitty graphics protocol, PNG files are sent as they are
+    Kitty,
+    /// Sixel, images are decoded and sent as pixels
+    Sixel,
+}
+
+/// Which protocol a terminal supports, from its answer to [QUERY]
+pub fn parse(response: &[u8]) -> Option<Protocol> {
+    if find(response, b"\x1b_Gi=31;OK").is_some() {
+        return Some(Protocol::Kitty);
+    }
+
+    // Attributes are numbers separated by semicolons, 4 is sixel
+    let start = find(response, b"\x1b[?")? + 3;
+    let end = start + response[start..].iter().position(|b| *b == b'c')?;
+    let attributes = std::str::from_utf8(&response[start..end]).ok()?;
+    attributes
+        .split(';')
+        .any(|attribute| attribute == "4")
+        .then_some(Protocol::Sixel)
+}
+
+/// Asks the terminal which protocol it supports, if any
+///
+/// Reads the answer from stdin, so it has to run in raw mode, before anything else reads it.
+pub fn detect() -> Option<Protocol> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{QUERY}").ok()?;
+    stdout.flush().ok()?;
+
+    let mut response = vec![];
+    let mut deadline = Instant::now() + TIMEOUT;
+    while !answered(&response) {
+        let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+            break;
+        };
+        let mut stdin = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Safety: poll only writes to `revents`
+        #[allow(unsafe_code)]
+        let ready = unsafe { libc::poll(&mut stdin, 1, timeout.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+
+        // As big as the buffer of stdin, so it's read directly and nothing stays buffered
+        let mut buf = [0; 8192];
+        let n = io::stdin().read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        // Once a slow terminal starts answering, it gets time to finish. The rest would be read
+        // as keys, and its Esc quits Gyr
+        if response.is_empty() {
+            deadline = Instant::now() + DRAIN;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+
+    parse(&response)
+}
+
+/// Whether `response` has the device attributes, they come last
+fn answered(response: &[u8]) -> bool {
+    find(response, b"\x1b[?").is_some_and(|start| response[start..].contains(&b'c'))
+}
+
+/// Size of a cell in pixels, width and height
+fn cell_size() -> (u16, u16) {
+    match (termion::terminal_size_pixels(), termion::terminal_size()) {
+        (Ok((width, height)), Ok((columns, rows))) if width > 0 && columns > 0 && rows > 0 => {
+            ((width / columns).max(1), (height / rows).max(1))
+        }
+        _ => CELL,
+    }
+}
+
+/// Draws an icon next to the app list, with one of the graphics [Protocol]s
+///
+/// Images aren't part of the terminal's text, so they're drawn after the rest of the UI, only
+/// when they change.
+pub struct Graphics {
+    protocol: Protocol,
+    /// Size of a cell in pixels
+    cell: (u16, u16),
+    /// Icon to draw, and its contents
+    icon: Option<(PathBuf, Image)>,
+    /// Icon drawn and where, if any
+    drawn: Option<(PathBuf, Rect)>,
+    /// Whether it has to be drawn again anyway
+    dirty: bool,
+}
+
+/// Contents of an icon file, ready for the protocol
+enum Image {
+    /// PNG file, for kitty
+    Png(Vec<u8>),
+    /// Decoded image, for sixel, and the last encoding
+    Pixels(Pixels, Option<(Rect, Vec<u8>)>),
+}
+
+/// Decoded image
+#[derive(Debug, Clone)]
+struct Pixels {
+    width: u32,
+    height: u32,
+    /// RGBA, row by row
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Graphics {
+    /// Draws with `protocol`
+    pub fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            cell: cell_size(),
+            icon: None,
+            drawn: None,
+            dirty: false,
+        }
+    }
+
+    /// Which protocol it draws with
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Columns taken by a square icon `rows` high
+    pub fn columns(&self, rows: u16) -> u16 {
+        let (width, height) = self.cell;
+        (u32::from(rows) * u32::from(height)).div_ceil(u32::from(width)) as u16
+    }
+
+    /// Pixels in `rows` lines, to pick an icon size
+    pub fn pixels(&self, rows: u16) -> u32 {
+        u32::from(rows) * u32::from(self.cell.1)
+    }
+
+    /// Reads the icon at `path`, to draw it next. Returns whether it can be drawn
+    pub fn load(&mut self, path: Option<&Path>) -> bool {
+        let Some(path) = path else {
+            self.icon = None;
+            return false;
+        };
+        if self.icon.as_ref().is_some_and(|(loaded, _)| loaded == path) {
+            return true;
+        }
+
+        let image = fs::read(path).ok().and_then(|data| match self.protocol {
+            // Kitty would tell us if it's broken, but we don't read its answers
+            Protocol::Kitty => data.starts_with(PNG).then_some(Image::Png(data)),
+            Protocol::Sixel => decode(&data).ok().map(|image| Image::Pixels(image, None)),
+        });
+        self.icon = image.map(|image| (path.to_path_buf(), image));
+        self.icon.is_some()
+    }
+
+    /// Whether the loaded icon is different from the one drawn
+    pub fn changed(&self, area: Option<Rect>) -> bool {
+        let loaded = self.icon.as_ref().map(|(path, _)| path);
+        let drawn = self.drawn.as_ref().map(|(path, drawn)| (path, *drawn));
+        self.dirty || drawn != loaded.zip(area)
+    }
+
+    /// Draws the icon again next time, the terminal was cleared or resized
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+        self.cell = cell_size();
+    }
+
+    /// Draws the loaded icon in `area`, removing the one drawn before
+    pub fn draw(&mut self, out: &mut impl Write, area: Option<Rect>) -> io::Result<()> {
+        if !self.changed(area) {
+            return Ok(());
+        }
+        self.dirty = false;
+        // Sixel images are erased by clearing the terminal before drawing, they're just pixels
+        if self.drawn.take().is_some() && self.protocol == Protocol::Kitty {
+            write!(out, "\x1b_Ga=d,d=A,q=2\x1b\\")?;
+        }
+
+        let (Some((path, image)), Some(area)) = (&mut self.icon, area) else {
+            return out.flush();
+        };
+        write!(
+            out,
+            "{}{}",
+            termion::cursor::Save,
+            termion::cursor::Goto(area.x + 1, area.y + 1)
+        )?;
+        match image {
+            Image::Png(data) => kitty(out, data, area)?,
+            Image::Pixels(image, encoded) => {
+                if !encoded.as_ref().is_some_and(|(size, _)| *size == area) {
+                    let size = u32::from(area.height) * u32::from(self.cell.1);
+                    *encoded = Some((area, sixel(image, size)));
+                }
+                if let Some((_, sixel)) = encoded {
+                    out.write_all(sixel)?;
+                }
+            }
+        }
+        write!(out, "{}", termion::cursor::Restore)?;
+        out.flush()?;
+
+        self.drawn = Some((path.clone(), area));
+        Ok(())
+    }
+}
+
+impl Drop for Graphics {
+    /// Kitty images aren't cleared with the text, inline Gyr would leave them behind
+    fn drop(&mut self) {
+        if self.drawn.is_some() && self.protocol == Protocol::Kitty {
+            let mut stdout = io::stdout();
+            write!(stdout, "\x1b_Ga=d,d=A,q=2\x1b\\").ok();
+            stdout.flush().ok();
+        }
+    }
+}
+
+/// Sends `png` with the kitty graphics protocol, scaled to the height of `area`
+fn kitty(out: &mut impl Write, png: &[u8], area: Rect) -> io::Result<()> {
+    let data = base64(png);
+    let mut chunks = data.chunks(KITTY_CHUNK).peekable();
+    let mut first = true;
+    while let Some(chunk) = chunks.next() {
+        let more = u8::from(chunks.peek().is_some());
+        // Don't answer (q=2), don't move the cursor (C=1), the width follows the height (r)
+        if first {
+            write!(out, "\x1b_Ga=T,f=100,q=2,C=1,r={},m={more};", area.height)?;
+        } else {
+            write!(out, "\x1b_Gm={more};")?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+        first = false;
+    }
+    Ok(())
+}
+
+/// Sixel image of `image`, scaled to fit in a square of `size` pixels
+///
+/// Colors are reduced to a 6x6x6 cube, mostly transparent pixels aren't drawn.
+fn sixel(image: &Pixels, size: u32) -> Vec<u8> {
+    let (width, height) = if image.width > image.height {
+        (size, (size * image.height / image.width).max(1))
+    } else {
+        ((size * image.width / image.height).max(1), size)
+    };
+    let pixels = scale(image, width, height);
+    let level = |channel: u8| (u16::from(channel) * 5 + 127) / 255;
+    let colors = pixels
+        .iter()
+        .map(|[r, g, b, a]| (*a >= 128).then(|| level(*r) * 36 + level(*g) * 6 + level(*b)))
+        .collect::<Vec<_>>();
+
+    let mut out = vec![];
+    // Transparent background (P2 = 1), square pixels, then the size
+    write!(out, "\x1bP0;1;0q\"1;1;{width};{height}").ok();
+    let mut used = [false; 216];
+    for color in colors.iter().flatten() {
+        used[*color as usize] = true;
+    }
+    for color in (0..216).filter(|c| used[*c]) {
+        let percent = |level: usize| level * 20;
+        write!(
+            out,
+            "#{color};2;{};{};{}",
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        )
+        .ok();
+    }
+
+    // Six rows at a time, one pass per color
+    let (width, height) = (width as usize, height as usize);
+    for top in (0..height).step_by(6) {
+        let rows = top..(top + 6).min(height);
+        let mut band = [false; 216];
+        for y in rows.clone() {
+            for color in colors[y * width..(y + 1) * width].iter().flatten() {
+                band[*color as usize] = true;
+            }
+        }
+
+        for color in (0..216).filter(|c| band[*c]) {
+            write!(out, "#{color}").ok();
+            let mut run: Option<(u8, usize)> = None;
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .filter(|y| colors[y * width + x] == Some(color as u16))
+                    .fold(0u8, |bits, y| bits | 1 << (y - top));
+                let sixel = 63 + bits;
+                run = match run {
+                    Some((previous, count)) if previous == sixel => Some((sixel, count + 1)),
+                    Some((previous, count)) => {
+                        repeat(&mut out, previous, count);
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            if let Some((sixel, count)) = run {
+                repeat(&mut out, sixel, count);
+            }
+            // Back to the start of the band, for the next color
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+/// Writes `sixel` `count` times, compressed if it's worth it
+fn repeat(out: &mut Vec<u8>, sixel: u8, count: usize) {
+    if count > 3 {
+        write!(out, "!{count}").ok();
+        out.push(sixel);
+    } else {
+        out.extend(std::iter::repeat_n(sixel, count));
+    }
+}
+
+/// `image` resized to `width` by `height`, averaging the pixels each one covers
+fn scale(image: &Pixels, width: u32, height: u32) -> Vec<[u8; 4]> {
+    let range = |i: u32, from: u32, to: u32| {
+        let start = i * from / to;
+        start as usize..((i + 1) * from / to).max(start + 1) as usize
+    };
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            // Weighted by alpha, so transparent pixels don't darken the edges
+            let (mut sum, mut alpha, mut count) = ([0u32; 3], 0u32, 0u32);
+            for sy in range(y, image.height, height) {
+                for sx in range(x, image.width, width) {
+                    let [r, g, b, a] = image.pixels[sy * image.width as usize + sx];
+                    let a = u32::from(a);
+                    for (sum, channel) in sum.iter_mut().zip([r, g, b]) {
+                        *sum += u32::from(channel) * a;
+                    }
+                    alpha += a;
+                    count += 1;
+                }
+            }
+            pixels.push(match alpha {
+                0 => [0; 4],
+                _ => [
+                    (sum[0] / alpha) as u8,
+                    (sum[1] / alpha) as u8,
+                    (sum[2] / alpha) as u8,
+                    (alpha / count) as u8,
+                ],
+            });
+        }
+    }
+    pixels
+}
+
+/// Decodes a PNG file, checking its checksums and size
+fn decode(data: &[u8]) -> Result<Pixels, png::DecodingError> {
+    let mut decoder = png::Decoder::new_with_limits(data, png::Limits { bytes: MAX_BYTES });
+    decoder.ignore_checksums(false);
+    // Palettes, transparent colors and small depths become 8 bit gray or RGB, with alpha or not
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(png::DecodingError::LimitsExceeded);
+    }
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    let samples = &buf[..frame.buffer_size()];
+    let pixels = match frame.color_type {
+        png::ColorType::Grayscale => samples.iter().map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => samples
+            .chunks_exact(2)
+            .map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Rgb => samples
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        // Palettes were expanded, indexed images never come out
+        png::ColorType::Rgba | png::ColorType::Indexed => samples
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect(),
+    };
+
+    Ok(Pixels {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+    })
+}
+
+/// Standard base64, with padding
+fn base64(data: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+    out
+}
+
+/// Position of `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_kitty() {
+        // kitty, then WezTerm, which also has sixel
+        let response = b"\x1b_Gi=31;OK\x1b\\\x1b[?62;c";
+        assert!(answered(response));
+        assert_eq!(parse(response), Some(Protocol::Kitty));
+        let response = b"\x1b_Gi=31;OK\x1b\\\x1b[?65;4;6;18;22c";
+        assert_eq!(parse(response), Some(Protocol::Kitty));
+    }
+
+    #[test]
+    fn parse_sixel() {
+        // xterm -ti vt340, then foot
+        let response = b"\x1b[?63;1;2;4;6;9;15;16;22;28c";
+        assert!(answered(response));
+        assert_eq!(parse(response), Some(Protocol::Sixel));
+        assert_eq!(parse(b"\x1b[?62;4;22c"), Some(Protocol::Sixel));
+    }
+
+    #[test]
+    fn parse_unsupported() {
+        // VTE, Alacritty, a kitty error, and attributes that only contain a 4
+        for response in [
+            &b"\x1b[?65;1;9c"[..],
+            b"\x1b[?6c",
+            b"\x1b_Gi=31;ENOTSUPPORTED:no\x1b\\\x1b[?62;c",
+            b"\x1b[?64;14;24c",
+        ] {
+            assert!(answered(response));
+            assert_eq!(parse(response), None);
+        }
+    }
+
+    #[test]
+    fn parse_partial() {
+        assert!(!answered(b""));
+        assert_eq!(parse(b""), None);
+        // The kitty answer came, the device attributes are still on their way
+        let response = b"\x1b_Gi=31;OK\x1b\\\x1b[?62;4";
+        assert!(!answered(response));
+        assert_eq!(parse(response), Some(Protocol::Kitty));
+        assert_eq!(parse(b"\x1b[?62;4"), None);
+    }
+
+    /// PNG file of `width` by `height` pixels, with `color` samples
+    fn encode(width: u32, height: u32, color: png::ColorType, samples: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(samples)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn decode_rgba() {
+        let data = encode(
+            2,
+            1,
+            png::ColorType::Rgba,
+            &[255, 0, 0, 255, 0, 0, 255, 128],
+        );
+        let image = decode(&data).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixels, [[255, 0, 0, 255], [0, 0, 255, 128]]);
+    }
+
+    #[test]
+    fn decode_gray() {
+        let data = encode(1, 2, png::ColorType::Grayscale, &[0, 200]);
+        let image = decode(&data).unwrap();
+        assert_eq!(image.pixels, [[0, 0, 0, 255], [200, 200, 200, 255]]);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let data = encode(16, 16, png::ColorType::Rgb, &[7; 16 * 16 * 3]);
+        for length in [4, PNG.len(), 40, data.len() - 20] {
+            assert!(decode(&data[..length]).is_err(), "{length} bytes");
+        }
+    }
+
+    #[test]
+    fn decode_bad_checksum() {
+        let mut data = encode(4, 4, png::ColorType::Rgb, &[7; 4 * 4 * 3]);
+        // Last byte of the IDAT CRC, IEND takes the last 12
+        let crc = data.len() - 13;
+        data[crc] ^= 1;
+        assert!(decode(&data).is_err());
+    }
+
+    #[test]
+    fn decode_oversized() {
+        let data = encode(
+            MAX_SIZE + 1,
+            1,
+            png::ColorType::Grayscale,
+            &[0; MAX_SIZE as usize + 1],
+        );
+        assert!(decode(&data).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::env;
+use std::fs;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::panic;
+use std::path;
+use std::process;
+use std::sync::Mutex;
+
+use eyre::WrapErr;
+
+use super::signal;
+
+/// Terminal settings before entering raw mode, restored by [restore]
+static TERMINAL: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// First line Gyr draws on (zero-based) when it's inline, see [Guard::inline]
+static INLINE: Mutex<Option<u16>> = Mutex::new(None);
+
+/// Where stderr goes while on the alternate screen, see [Guard::alternate_screen]
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// Stderr, captured while Gyr is on the alternate screen
+struct Capture {
+    /// Copy of the real stderr
+    stderr: libc::c_int,
+    /// What was written to stderr in the meantime
+    file: fs::File,
+}
+
+/// Cleans up after Gyr, whichever way it exits
+///
+/// * Early returns and normal exit: the terminal is restored when the guard is dropped.
+/// * Panics: the terminal is restored and the lockfile removed before printing the report.
+///   A panic in any thread is fatal, as we can't keep drawing after printing it.
+/// * Termination signals: handled by [signal], Gyr quits through the normal path.
+pub struct Guard {
+    _private: (),
+}
+
+impl Guard {
+    /// Installs the panic hook and the signal handlers
+    pub fn new(lock: &path::Path) -> eyre::Result<Self> {
+        let lock = lock.to_path_buf();
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            fs::remove_file(&lock).ok();
+            default_hook(info);
+            process::exit(101);
+        }));
+
+        signal::install().wrap_err("Failed to install signal handlers")?;
+
+        Ok(Self { _private: () })
+    }
+
+    /// Saves the terminal settings, call before entering raw mode.
+    ///
+    /// From now on, the terminal will be restored and cleared when Gyr exits.
+    pub fn save_terminal(&self) -> io::Result<()> {
+        // Safety: termios is a plain C struct, and tcgetattr fills it
+        #[allow(unsafe_code)]
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            *TERMINAL.lock().unwrap_or_else(|e| e.into_inner()) = Some(termios);
+        }
+        Ok(())
+    }
+
+    /// Switches to the alternate screen, so the terminal is left as it was when Gyr exits.
+    ///
+    /// Until then, stderr is written to a file instead of the screen we can't see, and printed
+    /// after leaving it. Panic reports included.
+    pub fn alternate_screen(&self) -> io::Result<()> {
+        let mut path = dirs::runtime_dir().unwrap_or_else(env::temp_dir);
+        path.push(format!(
+            "{}-stderr-{}.log",
+            env!("CARGO_PKG_NAME"),
+            process::id()
+        ));
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        // We keep our own handle
+        fs::remove_file(&path).ok();
+
+        // Safety: dup and dup2 only copy file descriptors, the file outlives the capture
+        #[allow(unsafe_code)]
+        let stderr = unsafe {
+            let stderr = libc::dup(libc::STDERR_FILENO);
+            if stderr < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO) < 0 {
+                let error = io::Error::last_os_error();
+                libc::close(stderr);
+                return Err(error);
+            }
+            stderr
+        };
+        *CAPTURE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Capture { stderr, file });
+
+        let mut stdout = io::stdout();
+        write!(stdout, "{}", termion::screen::ToAlternateScreen)?;
+        stdout.flush()
+    }
+
+    /// Gyr draws from line `top` (zero-based) down, keeping what's above.
+    ///
+    /// From now on, only those lines are cleared when Gyr exits.
+    pub fn inline(&self, top: u16) {
+        *INLINE.lock().unwrap_or_else(|e| e.into_inner()) = Some(top);
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// The terminal's stderr, for apps that outlive Gyr. `None` if it isn't being captured
+pub fn stderr() -> Option<fs::File> {
+    let capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    let stderr = capture.as_ref()?.stderr;
+    // Safety: the copy is owned by the returned file
+    #[allow(unsafe_code)]
+    unsafe {
+        let copy = libc::dup(stderr);
+        (copy >= 0).then(|| fs::File::from_raw_fd(copy))
+    }
+}
+
+/// Leaves raw mode, clears the screen (or the lines Gyr drew on, if it's inline, or leaves the
+/// alternate screen, printing what was written to stderr meanwhile) and shows the cursor.
+///
+/// Does nothing if the terminal wasn't saved, or was already restored.
+pub fn restore() {
+    let Some(termios) = TERMINAL.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+
+    // Safety: termios was filled by tcgetattr
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &termios);
+    }
+
+    let mut stdout = io::stdout();
+    if let Some(mut capture) = CAPTURE.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        write!(
+            stdout,
+            "{}{}",
+            termion::screen::ToMainScreen,
+            termion::cursor::Show
+        )
+        .ok();
+        stdout.flush().ok();
+
+        // Safety: the copy of stderr is ours, it's closed once it's back in place
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::dup2(capture.stderr, libc::STDERR_FILENO);
+            libc::close(capture.stderr);
+        }
+        if capture.file.seek(SeekFrom::Start(0)).is_ok() {
+            io::copy(&mut capture.file, &mut io::stderr()).ok();
+        }
+        return;
+    }
+
+    match *INLINE.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(top) => write!(
+            stdout,
+            "{}{}{}",
+            termion::cursor::Goto(1, top + 1),
+            termion::clear::AfterCursor,
+            termion::cursor::Show
+        ),
+        None => write!(
+            stdout,
+            "{}{}{}",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+            termion::cursor::Show
+        ),
+    }
+    .ok();
+    stdout.flush().ok();
+}
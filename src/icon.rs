@@ -0,0 +1,286 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Icon formats we can draw, SVG and XPM icons are skipped
+const EXTENSIONS: &[&str] = &["png"];
+
+/// Theme every other theme falls back to
+const FALLBACK_THEME: &str = "hicolor";
+
+/// How the icons of a theme directory can be scaled
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// Only their own size
+    Fixed,
+    /// Between these sizes
+    Scalable { min: u32, max: u32 },
+    /// Within this many pixels of their size
+    Threshold(u32),
+}
+
+/// Directory of a theme, with icons of one size
+#[derive(Debug)]
+struct Directory {
+    /// Relative to the theme
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: Kind,
+}
+
+impl Directory {
+    /// Whether its icons are meant for `size`
+    fn matches(&self, size: u32) -> bool {
+        match self.kind {
+            _ if self.scale != 1 => false,
+            Kind::Fixed => self.size == size,
+            Kind::Scalable { min, max } => (min..=max).contains(&size),
+            Kind::Threshold(threshold) => self.size.abs_diff(size) <= threshold,
+        }
+    }
+
+    /// How far its icons are from `size`
+    fn distance(&self, size: u32) -> u32 {
+        let (min, max) = match self.kind {
+            Kind::Fixed => (self.size, self.size),
+            Kind::Scalable { min, max } => (min, max),
+            Kind::Threshold(threshold) => {
+                (self.size.saturating_sub(threshold), self.size + threshold)
+            }
+        };
+        let (min, max) = (min * self.scale, max * self.scale);
+        if size < min {
+            min - size
+        } else {
+            size.saturating_sub(max)
+        }
+    }
+}
+
+/// Icon theme, read from its `index.theme`
+#[derive(Debug)]
+struct Theme {
+    /// The theme's directory in each base directory that has it
+    roots: Vec<PathBuf>,
+    directories: Vec<Directory>,
+    /// Themes to look in when this one doesn't have an icon
+    inherits: Vec<String>,
+}
+
+impl Theme {
+    /// Reads the theme called `name` from `base_dirs`, if it's there
+    fn read(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        let roots = base_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect::<Vec<_>>();
+        // The first index wins
+        let index = roots
+            .iter()
+            .find_map(|root| fs::read_to_string(root.join("index.theme")).ok())?;
+
+        // Keys of each section
+        let mut sections: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+        let mut section = "";
+        for line in index.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name;
+            } else if let Some((key, value)) = line.split_once('=') {
+                sections
+                    .entry(section)
+                    .or_default()
+                    .insert(key.trim(), value.trim());
+            }
+        }
+
+        let header = sections.get("Icon Theme")?;
+        let list = |key| {
+            header
+                .get(key)
+                .into_iter()
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let directories = list("Directories")
+            .filter_map(|path| {
+                let keys = sections.get(path)?;
+                let number = |key, default| {
+                    keys.get(key)
+                        .and_then(|value| value.parse().ok())
+                        .unwrap_or(default)
+                };
+                let size = number("Size", 0);
+                let kind = match keys.get("Type").copied() {
+                    Some("Fixed") => Kind::Fixed,
+                    Some("Scalable") => Kind::Scalable {
+                        min: number("MinSize", size),
+                        max: number("MaxSize", size),
+                    },
+                    _ => Kind::Threshold(number("Threshold", 2)),
+                };
+                Some(Directory {
+                    path: path.to_string(),
+                    size,
+                    scale: number("Scale", 1),
+                    kind,
+                })
+            })
+            .collect();
+
+        Some(Self {
+            roots,
+            directories,
+            inherits: list("Inherits").map(ToString::to_string).collect(),
+        })
+    }
+
+    /// Icon called `name` for `size`, or the closest to it
+    fn find(&self, name: &str, size: u32) -> Option<PathBuf> {
+        let mut closest: Option<(u32, PathBuf)> = None;
+        for directory in &self.directories {
+            // Only look for icons that would be closer than the closest one
+            let matches = directory.matches(size);
+            let distance = directory.distance(size);
+            if !matches && closest.as_ref().is_some_and(|(d, _)| *d <= distance) {
+                continue;
+            }
+
+            let Some(icon) = self
+                .roots
+                .iter()
+                .find_map(|root| file(&root.join(&directory.path), name))
+            else {
+                continue;
+            };
+            if matches {
+                return Some(icon);
+            }
+            closest = Some((distance, icon));
+        }
+        closest.map(|(_, icon)| icon)
+    }
+}
+
+/// Finds icons following the freedesktop icon theme specification
+///
+/// Looks in the theme, then in the themes it inherits from (and so on), then in hicolor, and
+/// finally in the base directories themselves, like `/usr/share/pixmaps`.
+#[derive(Debug)]
+pub struct Icons {
+    /// Themes to look in, in order
+    themes: Vec<Theme>,
+    base_dirs: Vec<PathBuf>,
+    /// Icons already looked up, by name and size
+    cache: HashMap<(String, u32), Option<PathBuf>>,
+}
+
+impl Icons {
+    /// Reads the icon theme called `theme`, and the ones it inherits from
+    pub fn new(theme: &str) -> Self {
+        let base_dirs = base_dirs();
+
+        let mut themes = vec![];
+        let mut seen = HashSet::new();
+        for name in [theme, FALLBACK_THEME] {
+            read_themes(name, &base_dirs, &mut seen, &mut themes);
+        }
+
+        Self {
+            themes,
+            base_dirs,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Path of `icon` (the `Icon` key of a desktop file) closest to `size` pixels
+    pub fn find(&mut self, icon: &str, size: u32) -> Option<PathBuf> {
+        let key = (icon.to_string(), size);
+        if let Some(path) = self.cache.get(&key) {
+            return path.clone();
+        }
+
+        let path = if Path::new(icon).is_absolute() {
+            Some(PathBuf::from(icon)).filter(|path| drawable(path) && path.is_file())
+        } else {
+            // Some desktop files name the icon with its extension
+            let name = match icon.rsplit_once('.') {
+                Some((name, "png" | "svg" | "xpm")) => name,
+                _ => icon,
+            };
+            self.themes
+                .iter()
+                .find_map(|theme| theme.find(name, size))
+                .or_else(|| self.base_dirs.iter().find_map(|dir| file(dir, name)))
+        };
+
+        self.cache.insert(key, path.clone());
+        path
+    }
+}
+
+/// Reads the theme called `name`, then the ones it inherits from, depth-first
+fn read_themes(
+    name: &str,
+    base_dirs: &[PathBuf],
+    seen: &mut HashSet<String>,
+    themes: &mut Vec<Theme>,
+) {
+    if !seen.insert(name.to_string()) {
+        return;
+    }
+    let Some(theme) = Theme::read(name, base_dirs) else {
+        return;
+    };
+    let parents = theme.inherits.clone();
+    themes.push(theme);
+    for parent in &parents {
+        read_themes(parent, base_dirs, seen, themes);
+    }
+}
+
+/// Where icon themes and loose icons are: `~/.icons`, `icons` in each data directory, and
+/// `/usr/share/pixmaps`
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".icons"));
+    }
+    if let Some(data) = dirs::data_local_dir() {
+        dirs.push(data.join("icons"));
+    }
+    match env::var("XDG_DATA_DIRS") {
+        Ok(data_dirs) => dirs.extend(
+            data_dirs
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| Path::new(dir).join("icons")),
+        ),
+        Err(_) => dirs.extend(
+            ["/usr/local/share/icons", "/usr/share/icons"]
+                .iter()
+                .map(PathBuf::from),
+        ),
+    }
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    dirs
+}
+
+/// Icon called `name` in `dir`, with any of the [EXTENSIONS]
+fn file(dir: &Path, name: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| dir.join(format!("{name}.{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// Whether `path` has one of the [EXTENSIONS]
+fn drawable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension))
+}
@@ -1,37 +1,40 @@
-use std::io;
+use std::io::{self, Read};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use termion::event::{self, Key, MouseEvent};
 use termion::input::TermRead;
 
 /// Builder for `Input`
 ///
-/// For now, you can only configure the exit key (Esc by default).
-/// But in the future, there may be some more interesting configuration options...
+/// For now, you can only configure the exit key (none by default, what a key does is up to the
+/// keymap) and the tick rate.
 ///
 /// # Example
-/// ```rust
-/// // Build a default `Input` (Esc ends the handling thread)
+/// ```rust,no_run
+/// # use gyr::input::Config;
+/// # use termion::event::Key;
+/// // Build a default `Input` (keys are read until the `Input` is dropped)
 /// let input = Config::default().init();
-/// // Customize the exit key
-/// let input = Config {
-///     exit_key: Key::Backspace,
-///     ..Default::default()
-/// }.init();
+/// // Stop reading keys after Esc
+/// let mut config = Config::default();
+/// config.exit_key = Some(Key::Esc);
+/// let input = config.init();
 /// ```
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
-    pub exit_key: Key,
+    /// Key that stops reading input, if any
+    pub exit_key: Option<Key>,
+    /// Time between [`Event::Tick`]s
     pub tick_rate: Duration,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            exit_key: Key::Esc,
+            exit_key: None,
             tick_rate: Duration::from_millis(250),
         }
     }
@@ -44,8 +47,15 @@ impl Config {
     }
 }
 
+/// Something that happened in the terminal
 pub enum Event<I> {
+    /// A key was pressed
     Input(I),
+    /// The mouse was clicked or scrolled
+    Mouse(MouseEvent),
+    /// The terminal was resized
+    Resize,
+    /// Nothing happened for a while
     Tick,
 }
 
@@ -53,14 +63,23 @@ pub enum Event<I> {
 pub struct Input {
     rx: mpsc::Receiver<Event<Key>>,
     _input_handle: thread::JoinHandle<()>,
+    _resize_handle: Option<thread::JoinHandle<()>>,
     _tick_handle: thread::JoinHandle<()>,
 }
 
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Input {
+    /// Starts reading input with the default [`Config`]
     pub fn new() -> Self {
         Config::default().init()
     }
 
+    /// Starts reading input with `config`
     pub fn with_config(config: Config) -> Self {
         let (tx, rx) = mpsc::channel();
 
@@ -69,16 +88,34 @@ impl Input {
 
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for key in stdin.keys().flatten() {
-                    if tx.send(Event::Input(key)).is_err() {
+                for event in stdin.events().flatten() {
+                    let event = match event {
+                        event::Event::Key(key) => Event::Input(key),
+                        event::Event::Mouse(mouse) => Event::Mouse(mouse),
+                        event::Event::Unsupported(_) => continue,
+                    };
+                    let exit = matches!(event, Event::Input(key) if Some(key) == config.exit_key);
+
+                    if tx.send(event).is_err() || exit {
                         return;
                     }
-                    if key == config.exit_key {
+                }
+            })
+        };
+
+        // Without it, resizes are noticed on the next tick
+        let _resize_handle = super::signal::resizes().ok().map(|mut resizes| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut buf = [0; 64];
+                // Many resizes in a row are drawn once
+                while matches!(resizes.read(&mut buf), Ok(n) if n > 0) {
+                    if tx.send(Event::Resize).is_err() {
                         return;
                     }
                 }
             })
-        };
+        });
 
         let _tick_handle = {
             thread::spawn(move || loop {
@@ -92,12 +129,22 @@ impl Input {
         Self {
             rx,
             _input_handle,
+            _resize_handle,
             _tick_handle,
         }
     }
 
-    /// Next key pressed by user.
+    /// Next key pressed or mouse event by user.
     pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
         self.rx.recv()
     }
+
+    /// Like [`Input::next`], but gives up after `timeout`, returning a tick.
+    pub fn next_timeout(&self, timeout: Duration) -> Result<Event<Key>, mpsc::RecvError> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(event) => Ok(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(Event::Tick),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(mpsc::RecvError),
+        }
+    }
 }
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+/// Something a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Quit without running anything
+    Quit,
+    /// Run the selected app and quit
+    Launch,
+    /// Run the selected app, without quitting
+    LaunchKeepOpen,
+    /// Select the next app
+    SelectNext,
+    /// Select the previous app
+    SelectPrev,
+    /// Select the first app
+    SelectFirst,
+    /// Select the last app
+    SelectLast,
+    /// Move the selection one page down
+    SelectPageDown,
+    /// Move the selection one page up
+    SelectPageUp,
+    /// Move the selection half a page down
+    SelectHalfPageDown,
+    /// Move the selection half a page up
+    SelectHalfPageUp,
+    /// Move the cursor one character to the left
+    CursorLeft,
+    /// Move the cursor one character to the right
+    CursorRight,
+    /// Move the cursor to the start of the query
+    CursorStart,
+    /// Move the cursor to the end of the query
+    CursorEnd,
+    /// Move the cursor one word to the left
+    WordLeft,
+    /// Move the cursor one word to the right
+    WordRight,
+    /// Remove the character before the cursor
+    DeleteCharBackward,
+    /// Remove the character under the cursor
+    DeleteChar,
+    /// Remove the previous word, separated by whitespace
+    KillWord,
+    /// Remove the previous alphanumeric word
+    KillAlphanumericWord,
+    /// Remove everything before the cursor
+    KillToStart,
+    /// Remove everything after the cursor
+    KillToEnd,
+    /// Insert the last removed text
+    Yank,
+    /// Remove the whole query
+    ClearQuery,
+    /// Switch between fuzzy and exact matching
+    ToggleMatcher,
+    /// Show the next category tab
+    NextCategory,
+    /// Show the previous category tab
+    PrevCategory,
+    /// List the actions of the selected app under it, or hide them
+    ToggleActions,
+    /// Enter vi normal mode
+    NormalMode,
+    /// Enter vi insert mode
+    InsertMode,
+    /// Enter vi insert mode after the cursor
+    AppendMode,
+}
+
+/// Action names, as written in the config file
+const ACTIONS: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("launch", Action::Launch),
+    ("launch-keep-open", Action::LaunchKeepOpen),
+    ("select-next", Action::SelectNext),
+    ("select-prev", Action::SelectPrev),
+    ("select-first", Action::SelectFirst),
+    ("select-last", Action::SelectLast),
+    ("select-page-down", Action::SelectPageDown),
+    ("select-page-up", Action::SelectPageUp),
+    ("select-half-page-down", Action::SelectHalfPageDown),
+    ("select-half-page-up", Action::SelectHalfPageUp),
+    ("cursor-left", Action::CursorLeft),
+    ("cursor-right", Action::CursorRight),
+    ("cursor-start", Action::CursorStart),
+    ("cursor-end", Action::CursorEnd),
+    ("word-left", Action::WordLeft),
+    ("word-right", Action::WordRight),
+    ("delete-char-backward", Action::DeleteCharBackward),
+    ("delete-char", Action::DeleteChar),
+    ("kill-word", Action::KillWord),
+    ("kill-alphanumeric-word", Action::KillAlphanumericWord),
+    ("kill-to-start", Action::KillToStart),
+    ("kill-to-end", Action::KillToEnd),
+    ("yank", Action::Yank),
+    ("clear-query", Action::ClearQuery),
+    ("toggle-matcher", Action::ToggleMatcher),
+    ("next-category", Action::NextCategory),
+    ("prev-category", Action::PrevCategory),
+    ("toggle-actions", Action::ToggleActions),
+    ("normal-mode", Action::NormalMode),
+    ("insert-mode", Action::InsertMode),
+    ("append-mode", Action::AppendMode),
+];
+
+/// Bindings used in insert mode, or all the time without vi mode
+const INSERT: &[(&str, Action)] = &[
+    ("esc", Action::Quit),
+    ("ctrl-q", Action::Quit),
+    ("ctrl-c", Action::Quit),
+    ("enter", Action::Launch),
+    ("ctrl-y", Action::Launch),
+    ("alt-enter", Action::LaunchKeepOpen),
+    ("down", Action::SelectNext),
+    ("ctrl-n", Action::SelectNext),
+    ("up", Action::SelectPrev),
+    ("ctrl-p", Action::SelectPrev),
+    ("alt-<", Action::SelectFirst),
+    ("alt->", Action::SelectLast),
+    ("pagedown", Action::SelectPageDown),
+    ("pageup", Action::SelectPageUp),
+    ("alt-n", Action::SelectHalfPageDown),
+    ("alt-p", Action::SelectHalfPageUp),
+    ("left", Action::CursorLeft),
+    ("ctrl-b", Action::CursorLeft),
+    ("right", Action::CursorRight),
+    ("ctrl-f", Action::CursorRight),
+    ("home", Action::CursorStart),
+    ("ctrl-a", Action::CursorStart),
+    ("end", Action::CursorEnd),
+    ("ctrl-e", Action::CursorEnd),
+    ("alt-b", Action::WordLeft),
+    ("alt-f", Action::WordRight),
+    ("backspace", Action::DeleteCharBackward),
+    ("delete", Action::DeleteChar),
+    ("ctrl-w", Action::KillWord),
+    ("alt-backspace", Action::KillAlphanumericWord),
+    ("ctrl-u", Action::KillToStart),
+    ("ctrl-k", Action::KillToEnd),
+    ("alt-y", Action::Yank),
+    ("ctrl-t", Action::ToggleMatcher),
+    ("tab", Action::NextCategory),
+    ("backtab", Action::PrevCategory),
+];
+
+/// Bindings used in vi normal mode
+const NORMAL: &[(&str, Action)] = &[
+    ("esc", Action::Quit),
+    ("q", Action::Quit),
+    ("ctrl-c", Action::Quit),
+    ("enter", Action::Launch),
+    ("alt-enter", Action::LaunchKeepOpen),
+    ("j", Action::SelectNext),
+    ("down", Action::SelectNext),
+    ("k", Action::SelectPrev),
+    ("up", Action::SelectPrev),
+    ("g", Action::SelectFirst),
+    ("G", Action::SelectLast),
+    ("pagedown", Action::SelectPageDown),
+    ("ctrl-f", Action::SelectPageDown),
+    ("pageup", Action::SelectPageUp),
+    ("ctrl-b", Action::SelectPageUp),
+    ("ctrl-d", Action::SelectHalfPageDown),
+    ("ctrl-u", Action::SelectHalfPageUp),
+    ("h", Action::CursorLeft),
+    ("left", Action::CursorLeft),
+    ("l", Action::CursorRight),
+    ("right", Action::CursorRight),
+    ("0", Action::CursorStart),
+    ("home", Action::CursorStart),
+    ("$", Action::CursorEnd),
+    ("end", Action::CursorEnd),
+    ("b", Action::WordLeft),
+    ("w", Action::WordRight),
+    ("X", Action::DeleteCharBackward),
+    ("x", Action::DeleteChar),
+    ("delete", Action::DeleteChar),
+    ("D", Action::KillToEnd),
+    ("p", Action::Yank),
+    ("c", Action::ClearQuery),
+    ("ctrl-t", Action::ToggleMatcher),
+    ("tab", Action::NextCategory),
+    ("backtab", Action::PrevCategory),
+    ("i", Action::InsertMode),
+    ("a", Action::AppendMode),
+];
+
+/// Vi mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Keys are typed into the query, unless bound
+    Insert,
+    /// Keys do nothing, unless bound
+    Normal,
+}
+
+/// Key bindings for each mode
+#[derive(Debug)]
+pub struct Keymap {
+    insert: HashMap<Key, Action>,
+    normal: HashMap<Key, Action>,
+}
+
+impl Keymap {
+    /// Creates the default keymap
+    ///
+    /// With `vi` set, Esc enters normal mode instead of quitting
+    pub fn new(vi: bool) -> Self {
+        let table = |bindings: &[(&str, Action)]| {
+            bindings
+                .iter()
+                .map(|(chord, action)| (parse_key(chord).unwrap(), *action))
+                .collect()
+        };
+
+        let mut keymap = Self {
+            insert: table(INSERT),
+            normal: table(NORMAL),
+        };
+        if vi {
+            keymap.insert.insert(Key::Esc, Action::NormalMode);
+        }
+        keymap
+    }
+
+    /// Binds `chord` to the action named `action` in `mode`. `"none"` removes the binding
+    pub fn bind(&mut self, mode: Mode, chord: &str, action: &str) -> Result<(), String> {
+        let key = parse_key(chord)?;
+        let map = match mode {
+            Mode::Insert => &mut self.insert,
+            Mode::Normal => &mut self.normal,
+        };
+
+        if action == "none" {
+            map.remove(&key);
+            return Ok(());
+        }
+
+        let Some((_, action)) = ACTIONS.iter().find(|(name, _)| *name == action) else {
+            let names = ACTIONS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            return Err(format!(
+                "unknown action \"{action}\" for \"{chord}\", expected one of: none, {}",
+                names.join(", ")
+            ));
+        };
+        map.insert(key, *action);
+        Ok(())
+    }
+
+    /// Action bound to `key` in `mode`
+    pub fn get(&self, mode: Mode, key: Key) -> Option<Action> {
+        // Some terminals send ^H instead of DEL after the escape, or CR for enter
+        let key = match key {
+            Key::Alt('\x08') => Key::Alt('\x7f'),
+            Key::Alt('\r') => Key::Alt('\n'),
+            key => key,
+        };
+
+        match mode {
+            Mode::Insert => self.insert.get(&key),
+            Mode::Normal => self.normal.get(&key),
+        }
+        .copied()
+    }
+}
+
+/// Parses a key chord, like `ctrl-n`, `alt-enter`, `pagedown` or `G`
+///
+/// Named keys and modifiers are case-insensitive, characters aren't. Terminals send `ctrl-j` and
+/// `ctrl-m` like enter, and `ctrl-i` like tab, so those are rejected instead of rebinding enter
+/// and tab
+fn parse_key(chord: &str) -> Result<Key, String> {
+    let (modifier, name) = match chord.split_once('-') {
+        // Not `-` itself, or `alt--`
+        Some((modifier, name)) if !modifier.is_empty() && !name.is_empty() => {
+            (Some(modifier.to_lowercase()), name)
+        }
+        _ => (None, chord),
+    };
+
+    let key = match name.to_lowercase().as_str() {
+        "enter" | "return" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "space" => Key::Char(' '),
+        "backspace" => Key::Backspace,
+        "esc" | "escape" => Key::Esc,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "backtab" => Key::BackTab,
+        function if function.starts_with('f') && function.len() > 1 => {
+            match function[1..].parse() {
+                Ok(n @ 1..=12) => Key::F(n),
+                _ => return Err(format!("unknown key \"{chord}\"")),
+            }
+        }
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c),
+                _ => return Err(format!("unknown key \"{chord}\"")),
+            }
+        }
+    };
+
+    let key = match (modifier.as_deref(), key) {
+        (None, key) => key,
+        (Some("alt"), Key::Char(c)) => Key::Alt(c),
+        (Some("alt"), Key::Backspace) => Key::Alt('\x7f'),
+        (Some("shift"), Key::Char('\t')) => Key::BackTab,
+        (Some("ctrl"), Key::Char(c)) => {
+            let c = c.to_ascii_lowercase();
+            match c {
+                // Terminals send these as other keys
+                'j' | 'm' => {
+                    return Err(format!(
+                        "\"{chord}\" can't be bound, terminals send it like enter"
+                    ))
+                }
+                'i' => {
+                    return Err(format!(
+                        "\"{chord}\" can't be bound, terminals send it like tab"
+                    ))
+                }
+                'a'..='z' | '4'..='7' => Key::Ctrl(c),
+                _ => return Err(format!("unknown key \"{chord}\"")),
+            }
+        }
+        _ => return Err(format!("unknown key \"{chord}\"")),
+    };
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_named() {
+        assert_eq!(parse_key("enter"), Ok(Key::Char('\n')));
+        assert_eq!(parse_key("Return"), Ok(Key::Char('\n')));
+        assert_eq!(parse_key("space"), Ok(Key::Char(' ')));
+        assert_eq!(parse_key("PageDown"), Ok(Key::PageDown));
+        assert_eq!(parse_key("f1"), Ok(Key::F(1)));
+        assert_eq!(parse_key("F12"), Ok(Key::F(12)));
+        // A character, not a function key
+        assert_eq!(parse_key("f"), Ok(Key::Char('f')));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("foo").is_err());
+        assert!(parse_key("").is_err());
+    }
+
+    #[test]
+    fn parse_chars() {
+        // Characters are case-sensitive
+        assert_eq!(parse_key("G"), Ok(Key::Char('G')));
+        assert_eq!(parse_key("g"), Ok(Key::Char('g')));
+        assert_eq!(parse_key("$"), Ok(Key::Char('$')));
+        assert_eq!(parse_key("-"), Ok(Key::Char('-')));
+    }
+
+    #[test]
+    fn parse_modifiers() {
+        assert_eq!(parse_key("ctrl-n"), Ok(Key::Ctrl('n')));
+        assert_eq!(parse_key("Ctrl-N"), Ok(Key::Ctrl('n')));
+        assert_eq!(parse_key("alt-b"), Ok(Key::Alt('b')));
+        assert_eq!(parse_key("alt-B"), Ok(Key::Alt('B')));
+        assert_eq!(parse_key("alt--"), Ok(Key::Alt('-')));
+        assert_eq!(parse_key("alt-enter"), Ok(Key::Alt('\n')));
+        assert_eq!(parse_key("alt-backspace"), Ok(Key::Alt('\x7f')));
+        assert_eq!(parse_key("shift-tab"), Ok(Key::BackTab));
+        assert!(parse_key("shift-a").is_err());
+        assert!(parse_key("alt-up").is_err());
+        assert!(parse_key("ctrl-1").is_err());
+        assert!(parse_key("super-a").is_err());
+    }
+
+    #[test]
+    fn parse_ctrl_aliases() {
+        // Terminals send these like enter and tab, binding them would rebind those
+        for chord in ["ctrl-j", "ctrl-m", "Ctrl-J"] {
+            let error = parse_key(chord).unwrap_err();
+            assert!(error.ends_with("terminals send it like enter"), "{error}");
+        }
+        let error = parse_key("ctrl-i").unwrap_err();
+        assert!(error.ends_with("terminals send it like tab"), "{error}");
+    }
+
+    #[test]
+    fn defaults() {
+        // Every default chord parses, or `Keymap::new` panics
+        let keymap = Keymap::new(false);
+        assert_eq!(keymap.get(Mode::Insert, Key::Esc), Some(Action::Quit));
+        assert_eq!(
+            keymap.get(Mode::Insert, Key::Ctrl('u')),
+            Some(Action::KillToStart)
+        );
+        let keymap = Keymap::new(true);
+        assert_eq!(keymap.get(Mode::Insert, Key::Esc), Some(Action::NormalMode));
+        assert_eq!(
+            keymap.get(Mode::Normal, Key::Char('j')),
+            Some(Action::SelectNext)
+        );
+        // Some terminals send CR for alt-enter
+        assert_eq!(
+            keymap.get(Mode::Insert, Key::Alt('\r')),
+            keymap.get(Mode::Insert, Key::Alt('\n'))
+        );
+    }
+
+    #[test]
+    fn bind() {
+        let mut keymap = Keymap::new(false);
+        keymap.bind(Mode::Insert, "ctrl-l", "clear-query").unwrap();
+        assert_eq!(
+            keymap.get(Mode::Insert, Key::Ctrl('l')),
+            Some(Action::ClearQuery)
+        );
+        // Enter stays bound
+        assert!(keymap.bind(Mode::Insert, "ctrl-j", "select-next").is_err());
+        assert_eq!(
+            keymap.get(Mode::Insert, Key::Char('\n')),
+            Some(Action::Launch)
+        );
+        keymap.bind(Mode::Insert, "esc", "none").unwrap();
+        assert_eq!(keymap.get(Mode::Insert, Key::Esc), None);
+        assert!(keymap.bind(Mode::Insert, "esc", "explode").is_err());
+        assert!(keymap.bind(Mode::Insert, "hyper-x", "quit").is_err());
+    }
+}
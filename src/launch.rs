@@ -0,0 +1,231 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eyre::{eyre, WrapErr};
+
+use super::cli;
+use super::guard;
+use super::xdg;
+
+/// Number of lines of stderr shown when an app fails to start
+const STDERR_LINES: usize = 10;
+
+/// Bytes of stderr kept while waiting for an app to fail, enough for [STDERR_LINES]
+const STDERR_BYTES: usize = 16 * 1024;
+
+/// An app that exited with an error right after being launched
+#[derive(Debug)]
+pub struct EarlyExit {
+    /// Exit status of the app
+    pub status: process::ExitStatus,
+    /// Last lines the app wrote to stderr
+    pub stderr: Vec<String>,
+}
+
+impl fmt::Display for EarlyExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Exited with {}", self.status)?;
+        for line in &self.stderr {
+            write!(f, "\n{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for EarlyExit {}
+
+/// Runs `app`
+///
+/// If `cli.launch_grace_period` isn't zero, waits that long for the app to fail, returning an
+/// [EarlyExit] error if it does. Apps run through `swaymsg` or the terminal launcher aren't
+/// watched, Gyr only sees those exit.
+///
+/// Errors are recoverable: the user can pick another app
+pub fn run(app: &xdg::App, cli: &cli::Opts) -> eyre::Result<()> {
+    // Split command in a shell-parseable format.
+    let commands = shell_words::split(&app.command)
+        .wrap_err_with(|| format!("Invalid Exec line for {app}: {}", app.command))?;
+
+    if commands.is_empty() {
+        return Err(eyre!("Empty Exec line for {app}"));
+    }
+
+    // Actual commands being run
+    let mut runner: Vec<&str> = vec![];
+
+    // Use `swaymsg` to run the command.
+    // Allows Sway to move the app to the workspace Gyr was run in.
+    if cli.sway {
+        runner.extend_from_slice(&["swaymsg", "exec", "--"]);
+    }
+
+    // Use terminal runner to run the app.
+    if app.is_terminal {
+        runner.extend_from_slice(&cli.terminal_launcher.split(' ').collect::<Vec<&str>>());
+    }
+
+    // Add app commands
+    runner.extend_from_slice(&commands.iter().map(AsRef::as_ref).collect::<Vec<&str>>());
+
+    let mut exec = process::Command::new(runner[0]);
+    exec.args(&runner[1..]);
+
+    // Run from the path specified by the app
+    if let Some(path) = &app.path {
+        if !path::Path::new(path).is_dir() {
+            return Err(eyre!(
+                "Failed to switch to {path} when starting {app}: no such directory"
+            ));
+        }
+        exec.current_dir(path);
+    }
+
+    // Set program as session leader.
+    // Otherwise the OS may kill the app after the Gyr exits.
+    //
+    // # Safety: pre_exec() isn't modifyng the memory and setsid() fails if the calling
+    // process is already a process group leader (which isn't)
+    #[allow(unsafe_code)]
+    unsafe {
+        exec.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+
+    // Not the captured stderr of Gyr, the app outlives it
+    if let Some(stderr) = guard::stderr() {
+        exec.stderr(stderr);
+    }
+
+    let quiet = cli.verbose.unwrap_or(0) > 0;
+    if quiet {
+        exec.stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null());
+    }
+
+    if cli.launch_grace_period.is_zero() || cli.sway || app.is_terminal {
+        exec.spawn()
+            .wrap_err_with(|| format!("Failed to run {exec:?}"))?;
+        return Ok(());
+    }
+
+    // Where stderr would have gone, it's handed back after the grace period
+    let target: Box<dyn Write + Send> = if quiet {
+        Box::new(io::sink())
+    } else if let Some(stderr) = guard::stderr() {
+        Box::new(stderr)
+    } else {
+        Box::new(io::stderr())
+    };
+
+    // Capture stderr in a pipe, held back during the grace period
+    let (mut stderr, writer) = io::pipe().wrap_err("Failed to capture stderr")?;
+    exec.stderr(writer);
+    // Nothing reads the pipe after Gyr exits, writing to it fails instead of killing the app
+    //
+    // # Safety: signal() is async-signal-safe
+    #[allow(unsafe_code)]
+    unsafe {
+        exec.pre_exec(|| {
+            libc::signal(libc::SIGPIPE, libc::SIG_IGN);
+            Ok(())
+        });
+    }
+    let mut child = exec
+        .spawn()
+        .wrap_err_with(|| format!("Failed to run {exec:?}"))?;
+    // Drop our copy of the writer, so the pipe ends with the app
+    drop(exec);
+
+    let mut captured = vec![];
+    let mut open = true;
+    let deadline = Instant::now() + cli.launch_grace_period;
+    while Instant::now() < deadline {
+        match child.try_wait()? {
+            Some(status) if !status.success() => {
+                // Whatever it wrote before exiting is waiting in the pipe
+                while open && ready(&stderr, Duration::ZERO) {
+                    open = read(&mut stderr, &mut captured);
+                }
+                let error = EarlyExit {
+                    status,
+                    stderr: tail(&captured),
+                };
+                return Err(eyre::Report::new(error).wrap_err(format!("{app} failed to start")));
+            }
+            Some(_) => break,
+            None if open && ready(&stderr, Duration::from_millis(20)) => {
+                open = read(&mut stderr, &mut captured);
+            }
+            None if open => {}
+            None => thread::sleep(Duration::from_millis(20)),
+        }
+    }
+
+    if open {
+        forward(stderr, captured, target);
+    } else {
+        let mut target = target;
+        target.write_all(&captured).ok();
+    }
+
+    Ok(())
+}
+
+/// Whether there's something to read in `pipe`, waiting up to `timeout`
+fn ready(pipe: &io::PipeReader, timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: pipe.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Safety: poll only writes to `revents`
+    #[allow(unsafe_code)]
+    let ready = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+    ready > 0
+}
+
+/// Reads what's in `pipe` into `captured`, keeping the last [STDERR_BYTES]. Returns whether the
+/// pipe is still open
+fn read(pipe: &mut io::PipeReader, captured: &mut Vec<u8>) -> bool {
+    let mut buf = [0; 4096];
+    let n = match pipe.read(&mut buf) {
+        Ok(0) | Err(_) => return false,
+        Ok(n) => n,
+    };
+    captured.extend_from_slice(&buf[..n]);
+    let excess = captured.len().saturating_sub(STDERR_BYTES);
+    captured.drain(..excess);
+    true
+}
+
+/// Writes `captured` to `target`, and then whatever the app writes to `pipe` until it closes it
+fn forward(mut pipe: io::PipeReader, captured: Vec<u8>, mut target: Box<dyn Write + Send>) {
+    thread::spawn(move || {
+        let copied = target
+            .write_all(&captured)
+            .and_then(|()| io::copy(&mut pipe, &mut target));
+        // Keep reading, the app blocks once the pipe is full
+        if copied.is_err() {
+            io::copy(&mut pipe, &mut io::sink()).ok();
+        }
+    });
+}
+
+/// Last [STDERR_LINES] lines of the captured stderr
+fn tail(captured: &[u8]) -> Vec<String> {
+    let contents = String::from_utf8_lossy(captured);
+    let lines = contents.lines().collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(STDERR_LINES)..]
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
@@ -0,0 +1,374 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ratatui::layout::{self, Constraint, Direction, Rect};
+use ratatui::text::Span;
+use ratatui::widgets::{Block, BorderType, Borders};
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+
+use super::theme::Theme;
+
+/// Where the query goes, relative to the app list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prompt {
+    /// Above the list
+    Top,
+    /// Below the list
+    Bottom,
+}
+
+/// Where the description of the selected app goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Description {
+    /// Above everything else
+    Top,
+    /// Below everything else
+    Bottom,
+    /// Right of the app list
+    Right,
+    /// Nowhere
+    Hidden,
+}
+
+/// Borders around each pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Border {
+    /// Rounded corners
+    Rounded,
+    /// Square corners
+    Plain,
+    /// Double lines
+    Double,
+    /// Thick lines
+    Thick,
+    /// No borders
+    None,
+}
+
+/// Size of the description pane: lines, or columns when it's on the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    /// Fixed size, borders included
+    Fixed(u16),
+    /// Percentage of the window
+    Percent(u16),
+}
+
+impl Size {
+    /// Lines (or columns) out of `total`
+    pub fn of(self, total: u16) -> u16 {
+        match self {
+            Size::Fixed(size) => size.min(total),
+            Size::Percent(percent) => (u32::from(total) * u32::from(percent) / 100) as u16,
+        }
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    /// Parses a number of lines, like `8`, or a percentage, like `30%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = match s.trim().strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|p| *p <= 100)
+                .map(Size::Percent),
+            None => s.trim().parse().ok().map(Size::Fixed),
+        };
+        size.ok_or_else(|| {
+            format!(
+                "invalid size \"{s}\", expected a number of lines or a percentage, like 8 or 30%"
+            )
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SizeVisitor;
+
+        impl<'de> Visitor<'de> for SizeVisitor {
+            type Value = Size;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a number of lines or a percentage, like 8 or \"30%\"")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Size, E> {
+                u16::try_from(v)
+                    .map(Size::Fixed)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Size, E> {
+                v.parse()
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(SizeVisitor)
+    }
+}
+
+/// Titles of each pane, as written in the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TitlesConf {
+    /// Title of the description
+    pub description: Option<String>,
+    /// Title of the app list
+    pub list: Option<String>,
+    /// Title of the query
+    pub prompt: Option<String>,
+}
+
+/// Layout, as written in the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConf {
+    /// Where the query goes
+    pub prompt: Option<Prompt>,
+    /// Where the description goes
+    pub description: Option<Description>,
+    /// Size of the description
+    pub description_size: Option<Size>,
+    /// Borders around each pane
+    pub border: Option<Border>,
+    /// Show category tabs above the app list
+    pub tabs: Option<bool>,
+    /// Titles of each pane
+    pub titles: Option<TitlesConf>,
+}
+
+/// Where each pane goes, and how it looks
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Where the query goes
+    pub prompt: Prompt,
+    /// Where the description goes
+    pub description: Description,
+    /// Size of the description
+    pub description_size: Size,
+    /// Borders around each pane
+    pub border: Border,
+    /// Show category tabs above the app list
+    pub tabs: bool,
+    /// Title of the description
+    pub description_title: String,
+    /// Title of the app list
+    pub list_title: String,
+    /// Title of the query
+    pub prompt_title: String,
+}
+
+/// Where each pane is drawn
+#[derive(Debug, Clone, Copy)]
+pub struct Areas {
+    /// Description of the selected app, if it's shown
+    pub description: Option<Rect>,
+    /// App list
+    pub list: Rect,
+    /// Query
+    pub prompt: Rect,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            prompt: Prompt::Bottom,
+            description: Description::Top,
+            description_size: Size::Fixed(8),
+            border: Border::Rounded,
+            tabs: false,
+            description_title: "Gyr".to_string(),
+            list_title: "Apps".to_string(),
+            prompt_title: String::new(),
+        }
+    }
+}
+
+impl Layout {
+    /// Replaces the parts of the layout set in `conf`
+    pub fn configure(&mut self, conf: LayoutConf) {
+        if let Some(prompt) = conf.prompt {
+            self.prompt = prompt;
+        }
+        if let Some(description) = conf.description {
+            self.description = description;
+        }
+        if let Some(size) = conf.description_size {
+            self.description_size = size;
+        }
+        if let Some(border) = conf.border {
+            self.border = border;
+        }
+        if let Some(tabs) = conf.tabs {
+            self.tabs = tabs;
+        }
+
+        let titles = conf.titles.unwrap_or_default();
+        if let Some(title) = titles.description {
+            self.description_title = title;
+        }
+        if let Some(title) = titles.list {
+            self.list_title = title;
+        }
+        if let Some(title) = titles.prompt {
+            self.prompt_title = title;
+        }
+    }
+
+    /// Splits `area` in panes
+    pub fn split(&self, area: Rect) -> Areas {
+        let split = |direction, area, constraints: [Constraint; 2]| {
+            let parts = layout::Layout::default()
+                .direction(direction)
+                .constraints(constraints.as_ref())
+                .split(area);
+            (parts[0], parts[1])
+        };
+        // The query has one line, plus its borders and title
+        let prompt_lines = self.chrome(&self.prompt_title) + 1;
+        let size = match self.description_size {
+            // Short windows (like inline ones) keep at least one app visible
+            Size::Fixed(size) if self.description != Description::Right => {
+                let rest = prompt_lines + self.chrome(&self.list_title) + u16::from(self.tabs) + 1;
+                Constraint::Length(size.min(area.height.saturating_sub(rest)))
+            }
+            Size::Fixed(size) => Constraint::Length(size),
+            Size::Percent(percent) => Constraint::Percentage(percent),
+        };
+
+        // The description goes around the list and the query, so they stay together
+        let (description, rest) = match self.description {
+            Description::Top => {
+                let (description, rest) =
+                    split(Direction::Vertical, area, [size, Constraint::Min(2)]);
+                (Some(description), rest)
+            }
+            Description::Bottom => {
+                let (rest, description) =
+                    split(Direction::Vertical, area, [Constraint::Min(2), size]);
+                (Some(description), rest)
+            }
+            Description::Right | Description::Hidden => (None, area),
+        };
+
+        let prompt_height = Constraint::Length(prompt_lines);
+        let (list, prompt) = match self.prompt {
+            Prompt::Top => {
+                let (prompt, list) = split(
+                    Direction::Vertical,
+                    rest,
+                    [prompt_height, Constraint::Min(1)],
+                );
+                (list, prompt)
+            }
+            Prompt::Bottom => split(
+                Direction::Vertical,
+                rest,
+                [Constraint::Min(1), prompt_height],
+            ),
+        };
+
+        // Only the list shares its lines with the description on the right
+        let (list, description) = match self.description {
+            Description::Right => {
+                let (list, description) =
+                    split(Direction::Horizontal, list, [Constraint::Min(1), size]);
+                (list, Some(description))
+            }
+            _ => (list, description),
+        };
+
+        Areas {
+            description,
+            list,
+            prompt,
+        }
+    }
+
+    /// Block around a pane, with `title` if it's not empty
+    pub fn block<'a>(&self, title: &'a str, theme: &Theme) -> Block<'a> {
+        let border_type = match self.border {
+            Border::Rounded => BorderType::Rounded,
+            Border::Plain => BorderType::Plain,
+            Border::Double => BorderType::Double,
+            Border::Thick => BorderType::Thick,
+            Border::None => BorderType::Plain,
+        };
+        let borders = match self.border {
+            Border::None => Borders::NONE,
+            _ => Borders::ALL,
+        };
+
+        let block = Block::default()
+            .borders(borders)
+            .border_type(border_type)
+            .border_style(theme.border);
+        // Even without borders, titles take a line
+        if title.is_empty() {
+            block
+        } else {
+            block.title(Span::styled(title, theme.title))
+        }
+    }
+
+    /// Lines taken by the borders and the title of a pane
+    fn chrome(&self, title: &str) -> u16 {
+        match (self.border, title.is_empty()) {
+            (Border::None, true) => 0,
+            (Border::None, false) => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size() {
+        assert_eq!("8".parse(), Ok(Size::Fixed(8)));
+        assert_eq!(" 8 ".parse(), Ok(Size::Fixed(8)));
+        assert_eq!("30%".parse(), Ok(Size::Percent(30)));
+        assert_eq!("30 %".parse(), Ok(Size::Percent(30)));
+        assert_eq!("100%".parse(), Ok(Size::Percent(100)));
+        assert_eq!("0%".parse(), Ok(Size::Percent(0)));
+        for invalid in ["", "%", "101%", "-1", "8 lines", "30%%", "1.5", "70000"] {
+            assert!(invalid.parse::<Size>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn size_of() {
+        assert_eq!(Size::Fixed(8).of(40), 8);
+        // Never bigger than the window
+        assert_eq!(Size::Fixed(80).of(40), 40);
+        assert_eq!(Size::Percent(30).of(40), 12);
+        assert_eq!(Size::Percent(100).of(u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn deserialize_size() {
+        #[derive(Deserialize)]
+        struct Conf {
+            size: Size,
+        }
+        let size = |raw: &str| toml::from_str::<Conf>(raw).map(|conf| conf.size);
+
+        assert_eq!(size("size = 8").unwrap(), Size::Fixed(8));
+        assert_eq!(size("size = \"8\"").unwrap(), Size::Fixed(8));
+        assert_eq!(size("size = \"30%\"").unwrap(), Size::Percent(30));
+        assert!(size("size = -1").is_err());
+        assert!(size("size = \"big\"").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+#![deny(unsafe_code)]
+#![deny(missing_docs)]
+
+//! # Gyr
+//!
+//! The modules behind the `gyr` binary, in a library so the benches can reach them
+
+/// Desktop entry cache
+pub mod cache;
+/// CLI parser
+pub mod cli;
+/// Control socket
+pub mod control;
+/// Client/daemon mode
+pub mod daemon;
+/// Glyphs next to app names
+pub mod glyph;
+/// Terminal graphics
+pub mod graphics;
+/// Cleanup on exit
+pub mod guard;
+/// Icon theme lookup
+pub mod icon;
+/// Terminal input helpers
+pub mod input;
+/// Key bindings
+pub mod keymap;
+/// App launcher
+pub mod launch;
+/// Pane placement
+pub mod layout;
+/// Single instance lock
+pub mod lock;
+/// Background matching
+pub mod matcher;
+/// Desktop notifications
+pub mod notify;
+/// Termination signals
+pub mod signal;
+/// Colors and styles
+pub mod theme;
+/// Ui helpers
+pub mod ui;
+/// Directory watcher
+#[cfg(target_os = "linux")]
+pub mod watch;
+/// XDG apps
+pub mod xdg;
+
+/// Byte packer and unpacker
+mod bytes {
+    /// Unacks an `[u8; 8]` array into a single `u64`, previously packed with [pack]
+    ///
+    /// [pack]: pack
+    pub const fn unpack(buffer: [u8; 8]) -> u64 {
+        let mut data = 0u64;
+        data |= buffer[0] as u64;
+        data |= (buffer[1] as u64) << 8;
+        data |= (buffer[2] as u64) << 16;
+        data |= (buffer[3] as u64) << 24;
+        data |= (buffer[4] as u64) << 32;
+        data |= (buffer[5] as u64) << 40;
+        data |= (buffer[6] as u64) << 48;
+        data |= (buffer[7] as u64) << 56;
+        data
+    }
+
+    /// Packs an `u64` into a `[u8; 8]` array.
+    ///
+    /// Can be unpacked with [unpack].
+    ///
+    /// [unpack]: unpack
+    pub const fn pack(data: u64) -> [u8; 8] {
+        let mut buffer = [0u8; 8];
+        buffer[0] = (data & 0xFF) as u8;
+        buffer[1] = ((data >> 8) & 0xFF) as u8;
+        buffer[2] = ((data >> 16) & 0xFF) as u8;
+        buffer[3] = ((data >> 24) & 0xFF) as u8;
+        buffer[4] = ((data >> 32) & 0xFF) as u8;
+        buffer[5] = ((data >> 40) & 0xFF) as u8;
+        buffer[6] = ((data >> 48) & 0xFF) as u8;
+        buffer[7] = ((data >> 56) & 0xFF) as u8;
+        buffer
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eyre::{eyre, WrapErr};
+
+/// How long to wait for a replaced instance to quit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Single instance lock
+///
+/// Held with an advisory `flock(2)` on the lockfile, so the kernel releases it even if Gyr crashes
+/// or gets killed. The lockfile contains the PID of the instance holding it.
+///
+/// The lockfile is removed when the lock is dropped.
+pub struct Lock {
+    path: path::PathBuf,
+    // Closing the file releases the lock, so it must be dropped last
+    _file: fs::File,
+}
+
+impl Lock {
+    /// Takes the lock at `path`.
+    ///
+    /// If another Gyr instance holds it, fails, or asks the instance to quit if `replace` is set.
+    pub fn acquire(path: &path::Path, replace: bool) -> eyre::Result<Self> {
+        let deadline = Instant::now() + REPLACE_TIMEOUT;
+        let mut replaced = None;
+
+        loop {
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .wrap_err("Failed to open lockfile")?;
+
+            if try_lock(&file).wrap_err("Failed to lock lockfile")? {
+                // The previous owner may have removed the file after we opened it, in which case
+                // we locked a file nobody else will see
+                if !is_same_file(&file, path) {
+                    continue;
+                }
+
+                // Write current pid to lock file
+                file.set_len(0)?;
+                file.write_all(process::id().to_string().as_bytes())?;
+
+                return Ok(Self {
+                    path: path.to_path_buf(),
+                    _file: file,
+                });
+            }
+
+            if !replace {
+                // gyr is already running
+                return Err(eyre!("Gyr is already running"));
+            }
+
+            match replaced {
+                None => {
+                    let mut contents = String::new();
+                    file.read_to_string(&mut contents)
+                        .wrap_err("Failed to read lockfile")?;
+                    let pid: i32 = contents
+                        .trim()
+                        .parse()
+                        .wrap_err("Failed to parse lockfile contents")?;
+
+                    // Don't signal some random process that reused the PID
+                    if !is_gyr(pid) {
+                        return Err(eyre!(
+                            "Lockfile {} is held by process {pid}, which isn't Gyr",
+                            path.display()
+                        ));
+                    }
+
+                    // Safety: kill() has no memory safety preconditions
+                    #[allow(unsafe_code)]
+                    unsafe {
+                        libc::kill(pid, libc::SIGTERM);
+                    }
+                    replaced = Some(pid);
+                }
+                Some(pid) if Instant::now() > deadline => {
+                    return Err(eyre!("Gyr instance {pid} didn't quit"));
+                }
+                Some(_) => thread::sleep(Duration::from_millis(50)),
+            }
+        }
+    }
+
+    /// Path of the lockfile
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Remove it while still holding the lock, see `is_same_file`
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Tries to take an exclusive lock on `file`, without blocking
+fn try_lock(file: &fs::File) -> io::Result<bool> {
+    // Safety: the file descriptor is valid for the lifetime of `file`
+    #[allow(unsafe_code)]
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let error = io::Error::last_os_error();
+    if error.kind() == io::ErrorKind::WouldBlock {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+/// Whether `file` is still the file at `path`
+fn is_same_file(file: &fs::File, path: &path::Path) -> bool {
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `pid` is a running Gyr process
+#[cfg(target_os = "linux")]
+fn is_gyr(pid: i32) -> bool {
+    // Compare with our own name, in case the binary was renamed
+    match (
+        fs::read(format!("/proc/{pid}/comm")),
+        fs::read("/proc/self/comm"),
+    ) {
+        (Ok(other), Ok(own)) => other == own,
+        _ => false,
+    }
+}
+
+/// Whether `pid` is a running Gyr process
+///
+/// Without procfs we can only check that the process exists
+#[cfg(not(target_os = "linux"))]
+fn is_gyr(pid: i32) -> bool {
+    // Safety: kill() with signal 0 only checks for the process existence
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::kill(pid, 0) == 0
+    }
+}
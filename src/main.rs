@@ -7,53 +7,72 @@
 //!
 //! For more info, check the [README](https://sr.ht/~f9/gyr)
 
-/// CLI parser
-mod cli;
-/// Terminal input helpers
-mod input;
-/// Ui helpers
-mod ui;
-/// XDG apps
-mod xdg;
+use gyr::{
+    cache, cli, control, daemon, graphics, guard, icon, input, keymap, launch, layout, lock,
+    notify, signal, theme, ui, xdg,
+};
 
+use graphics::{Graphics, Protocol};
 use input::{Event, Input};
+use keymap::Action;
 use ui::UI;
 
 use std::env;
 use std::fs;
-use std::io::{self, Write};
-use std::os::unix::process::CommandExt;
-use std::path;
+use std::io;
 use std::process;
-use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 use directories::ProjectDirs;
 use eyre::eyre;
 use eyre::WrapErr;
 use ratatui::backend::TermionBackend;
-use ratatui::layout::{Alignment, Constraint, Direction, Layout};
-use ratatui::style::{Modifier, Style};
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::Style;
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Wrap};
-use ratatui::Terminal;
-use termion::event::Key;
+use ratatui::widgets::{List, ListItem, ListState, Paragraph, Tabs, Wrap};
+use ratatui::{Terminal, TerminalOptions, Viewport};
+use termion::event::{Key, MouseButton, MouseEvent};
 use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 
+/// Two clicks on the same app closer than this are a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// How often the list is redrawn while matching
+const REDRAW: Duration = Duration::from_millis(30);
+
+/// Lines taken by the icon of the selected app
+const ICON_ROWS: u16 = 4;
+
+/// Columns left for the description next to the icon, at least
+const ICON_MIN_TEXT: u16 = 12;
+
+/// Where Gyr draws. Raw mode and mouse reporting are set up separately, so the terminal can be
+/// created again when an inline one is resized
+type Backend = TermionBackend<io::Stdout>;
+
 fn main() {
-    if let Err(error) = real_main() {
+    let cli = cli::parse();
+    let error_prompt = cli.as_ref().map_or(true, |cli| cli.error_prompt);
+
+    if let Err(error) = cli.map_err(eyre::Report::from).and_then(real_main) {
         eprintln!("{error:?}\n");
-        eprintln!("Press enter...");
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).ok();
+        // Wait, so the error can be read before the terminal closes
+        if error_prompt {
+            eprintln!("Press enter...");
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).ok();
+        }
         process::exit(1);
     }
 }
 
-fn real_main() -> eyre::Result<()> {
-    let cli = cli::parse()?;
-    let db: sled::Db;
-    let lock_path: path::PathBuf;
+fn real_main(cli: cli::Opts) -> eyre::Result<()> {
+    let mut index: Index;
+    let _lock: lock::Lock;
+    let _control: Option<control::Server>;
+    let guard: guard::Guard;
 
     // Open sled database
     if let Some(project_dirs) = ProjectDirs::from("me", "nkeor", env!("CARGO_PKG_NAME")) {
@@ -70,62 +89,67 @@ fn real_main() -> eyre::Result<()> {
             }
         }
 
+        // Run the daemon instead of the UI. It doesn't take the lock, as it can run alongside Gyr
+        if cli.daemon {
+            hist_db.push("hist_db");
+            return daemon::run(xdg::data_dirs()?, hist_db);
+        }
+
+        // Close the running instance instead of opening a new one
+        if cli.toggle && control::quit_running()? {
+            return Ok(());
+        }
+
         // Check if Gyr is already running
         {
-            let mut lock = hist_db.clone();
-            lock.push("lock");
-            lock_path = lock;
-            let contents = match fs::read_to_string(&lock_path) {
-                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
-                Ok(c) => c,
-                Err(e) => {
-                    return Err(e).wrap_err("Failed to read lockfile");
-                }
-            };
+            let mut lock_path = hist_db.clone();
+            lock_path.push("lock");
+            _lock = lock::Lock::acquire(&lock_path, cli.replace)?;
+        }
 
-            if !contents.is_empty() {
-                if cli.replace {
-                    let pid: i32 = contents
-                        .parse()
-                        .wrap_err("Failed to parse lockfile contents")?;
-                    #[allow(unsafe_code)]
-                    unsafe {
-                        libc::kill(pid, libc::SIGTERM);
-                    }
-                    fs::remove_file(&lock_path)?;
-                    std::thread::sleep(std::time::Duration::from_millis(200));
-                } else {
-                    // gyr is already running
-                    return Err(eyre!("Gyr is already running"));
+        // Clean up on panic, and quit cleanly when killed or when asked by `--toggle`
+        guard = guard::Guard::new(_lock.path())?;
+        // Only `--toggle` needs it, Gyr works fine without
+        _control = control::Server::start()
+            .map_err(|error| {
+                if cli.verbose.is_some() {
+                    eprintln!("{error:#}, --toggle won't close this instance");
                 }
-            }
+            })
+            .ok();
 
-            // Write current pid to lock file
-            let mut lock_file = fs::File::create(&lock_path)?;
-            let pid;
-            // Safety: call to getpid is safe
-            #[allow(unsafe_code)]
-            unsafe {
-                pid = libc::getpid();
-            }
-            lock_file.write_all(pid.to_string().as_bytes())?;
+        // The daemon holds the database, so only open it when it's not running
+        if let Some(client) = daemon::Client::connect() {
+            index = Index::Daemon(client);
+        } else {
+            hist_db.push("hist_db");
+            index = Index::Local(sled::open(hist_db).wrap_err("Failed to open database")?);
         }
 
-        hist_db.push("hist_db");
-
-        db = sled::open(hist_db).wrap_err("Failed to open database")?;
-
         if cli.clear_history {
-            db.clear().wrap_err("Error clearing database")?;
+            match &mut index {
+                Index::Local(db) | Index::Fallback(db) => xdg::AppHistory::new(db)
+                    .and_then(|history| history.clear())
+                    .wrap_err("Error clearing database")?,
+                Index::Daemon(client) => client.clear_history()?,
+            }
             println!("Database cleared succesfully!");
             println!(
                 "Note: to completely remove all traces of the database,
                 remove {}.",
                 project_dirs.data_local_dir().display()
             );
-            fs::remove_file(lock_path).wrap_err("Failed to remove lock file")?;
             return Ok(());
         }
+
+        if cli.rebuild_cache {
+            match &mut index {
+                Index::Local(db) | Index::Fallback(db) => {
+                    cache::Cache::clear(db).wrap_err("Error clearing cache")?
+                }
+                Index::Daemon(client) => client.rebuild_cache()?,
+            }
+        }
     } else {
         return Err(eyre!(
             "can't find data dir for {}, is your system broken?",
@@ -133,358 +157,711 @@ fn real_main() -> eyre::Result<()> {
         ));
     };
 
-    // Directories to look for applications
-    let mut dirs: Vec<path::PathBuf> = vec![];
-    if let Ok(res) = env::var("XDG_DATA_DIRS") {
-        for data_dir in res.split(':') {
-            let mut dir = path::PathBuf::from(data_dir);
-            dir.push("applications");
-            if dir.exists() {
-                dirs.push(dir.clone());
-            }
-        }
-    } else {
-        for data_dir in &mut [
-            // Data directories
-            path::PathBuf::from("/usr/share"),
-            path::PathBuf::from("/usr/local/share"),
-            dirs::data_local_dir().ok_or_else(|| eyre!("failed to get local data dir"))?,
-        ] {
-            // Add `/applications`
-            data_dir.push("applications");
-            if data_dir.exists() {
-                dirs.push(data_dir.clone());
-            }
-        }
-    }
-
     // Read applications
-    let apps = xdg::read(dirs, &db);
+    let apps = match &mut index {
+        Index::Local(db) | Index::Fallback(db) => xdg::read(
+            xdg::data_dirs()?,
+            db,
+            cli.live_reload,
+            cli.verbose.is_some(),
+        ),
+        Index::Daemon(client) => match client.read() {
+            Ok(apps) => apps,
+            // A stuck daemon still holds the database, so apps are read without their history,
+            // and launches can't be added to it
+            Err(error) => {
+                if cli.verbose.is_some() {
+                    eprintln!("{error:#}, reading apps without the daemon");
+                }
+                let db = sled::Config::new()
+                    .temporary(true)
+                    .open()
+                    .wrap_err("Failed to open database")?;
+                let apps = xdg::read(
+                    xdg::data_dirs()?,
+                    &db,
+                    cli.live_reload,
+                    cli.verbose.is_some(),
+                );
+                index = Index::Fallback(db);
+                apps
+            }
+        },
+    };
 
     // Initialize the terminal
-    let raw_handle = io::stdout()
-        .into_raw_mode()
-        .wrap_err("Failed to initialize raw stdout handle")?;
-    let stdout = io::stdout()
+    guard
+        .save_terminal()
+        .wrap_err("Failed to read terminal settings")?;
+    let raw = io::stdout()
         .into_raw_mode()
         .wrap_err("Failed to init stdout")?;
-    let stdout = MouseTerminal::from(stdout);
-    let backend = TermionBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).wrap_err("Failed to start termion::Terminal")?;
-    // Clear terminal. We could use termion::screen::AlternateScreen, but then we lose panic!() and
-    // println!() output
+    let _mouse = MouseTerminal::from(raw);
+    // Inline terminals keep what's above them anyway
+    if cli.alternate_screen && cli.height.is_none() {
+        guard
+            .alternate_screen()
+            .wrap_err("Failed to enter the alternate screen")?;
+    }
+    let mut terminal = match cli.height {
+        None => Terminal::new(TermionBackend::new(io::stdout())),
+        Some(height) => inline_area(height).and_then(inline_terminal),
+    }
+    .wrap_err("Failed to start termion::Terminal")?;
+    if cli.height.is_some() {
+        guard.inline(terminal.get_frame().size().y);
+    }
+    // Clear terminal (only our lines, if inline). Unless `alternate_screen` is set, we draw on the
+    // main screen, where panic!() and println!() output is seen right away
     terminal.clear().wrap_err("Failed to clear terminal")?;
     terminal.hide_cursor().wrap_err("Failed to hide cursor")?;
+    // Terminal size, to notice when an inline terminal is resized
+    let mut size = termion::terminal_size().wrap_err("Failed to get terminal size")?;
+
+    // Icons are drawn with whichever protocol the terminal supports. Asking reads stdin, so it's
+    // done before the input handler starts
+    let mut graphics = match cli.icons {
+        cli::Icons::Auto => graphics::detect(),
+        cli::Icons::Kitty => Some(Protocol::Kitty),
+        cli::Icons::Sixel => Some(Protocol::Sixel),
+        cli::Icons::Text | cli::Icons::None => None,
+    }
+    .map(Graphics::new);
+    // The text fallback doesn't need the icon files
+    let mut icons = graphics
+        .is_some()
+        .then(|| icon::Icons::new(&cli.icon_theme));
 
     // Input handler
     let input = Input::new();
 
     // App UI
-    //
-    // Get one app to initialize the UI
-    let mut ui = UI::new(vec![apps.recv()?]);
+    let mut ui = UI::new(vec![]);
 
     // Set user-defined verbosity level
     if let Some(level) = cli.verbose {
         ui.verbosity(level);
     }
 
+    ui.sticky = cli.keep_selection;
+    ui.search_actions = cli.search_actions;
+
     // App list
     let mut app_state = ListState::default();
 
-    let mut app_loading_finished = false;
+    // App that was run
+    let mut launched = None;
+
+    // Vi mode, Gyr always starts inserting
+    let mut mode = keymap::Mode::Insert;
+
+    // Where the app list was drawn (inside its borders), for mouse clicks
+    let mut list_area = Rect::default();
+    // Where the icon of the selected app was drawn, if anywhere
+    let mut icon_area: Option<Rect> = None;
+    // Last clicked list row, to detect double clicks
+    let mut last_click: Option<(usize, Instant)> = None;
 
     loop {
-        if !app_loading_finished {
-            loop {
-                match apps.try_recv() {
-                    Ok(app) => {
-                        ui.hidden.push(app);
-                    }
-                    Err(e) => {
-                        match e {
-                            mpsc::TryRecvError::Disconnected => {
-                                // Done loading, add apps to the UI
-                                app_loading_finished = true;
-                                ui.filter();
-                                ui.info(cli.highlight_color);
-                            }
-                            mpsc::TryRecvError::Empty => (),
-                        }
-                        break;
-                    }
+        // Killed, quit without running anything
+        if signal::received() {
+            break;
+        }
+
+        loop {
+            match apps.try_recv() {
+                // Still loading, apps are added to the UI when done
+                Ok(xdg::Event::Add(app)) => {
+                    ui.add(app);
+                }
+                Ok(xdg::Event::Loaded) => {
+                    ui.filter();
+                    ui.info(cli.theme.name);
+                }
+                // Live reload
+                Ok(xdg::Event::Update(file, new)) => {
+                    ui.replace(&file, new);
+                    ui.info(cli.theme.name);
                 }
+                Ok(xdg::Event::Remove(path)) => {
+                    ui.replace(&path, vec![]);
+                    ui.info(cli.theme.name);
+                }
+                Err(_) => break,
+            }
+        }
+
+        // Show what matched so far
+        if ui.poll(Duration::ZERO) {
+            ui.info(cli.theme.name);
+        }
+
+        // Fixed viewports aren't resized by ratatui. Keep the top line, unless it doesn't fit
+        if let Some(height) = cli.height {
+            let new_size = termion::terminal_size().wrap_err("Failed to get terminal size")?;
+            if new_size != size {
+                size = new_size;
+                let (columns, rows) = size;
+                let lines = height.of(rows).max(1);
+                let top = terminal
+                    .get_frame()
+                    .size()
+                    .y
+                    .min(rows.saturating_sub(lines));
+                terminal = inline_terminal(Rect::new(0, top, columns, lines))
+                    .wrap_err("Failed to start termion::Terminal")?;
+                guard.inline(top);
+                terminal.clear().wrap_err("Failed to clear terminal")?;
+                terminal.hide_cursor().wrap_err("Failed to hide cursor")?;
+            }
+        }
+
+        // Icon of the selected app, if it can be drawn
+        let mut drawable = false;
+        if let (Some(graphics), Some(icons)) = (&mut graphics, &mut icons) {
+            let size = graphics.pixels(ICON_ROWS);
+            let path = ui.selected_app().and_then(|app| app.icon_path(icons, size));
+            drawable = graphics.load(path.as_deref());
+            // Sixel images stay until their cells are drawn over
+            if graphics.protocol() == Protocol::Sixel && graphics.changed(icon_area) {
+                terminal.clear().wrap_err("Failed to clear terminal")?;
+                graphics.invalidate();
             }
         }
 
         // Draw UI
         terminal.draw(|f| {
-            // Split the window in half.
-            //
-            // window[0] will hold the query, fixed length.
-            // window[1] will be split in two, list and query.
-            let window = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Length(8), Constraint::Min(2)].as_ref())
-                .split(f.size());
-
-            // Create a block.
-            //
-            // Rounded borders and bold title
-            let create_block = |title| {
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(Span::styled(
-                        title,
-                        Style::default().add_modifier(Modifier::BOLD),
-                    ))
-                    .border_type(BorderType::Rounded)
+            // Where each pane goes. Errors are shown in the description, even if it's hidden
+            let areas = if ui.failed && cli.layout.description == layout::Description::Hidden {
+                let layout = layout::Layout {
+                    description: layout::Description::Top,
+                    ..cli.layout.clone()
+                };
+                layout.split(f.size())
+            } else {
+                cli.layout.split(f.size())
             };
-
-            // Split window[1] horizontally.
-            //
-            // bottom_half[0] will hold the app list, miminum length 3.
-            // bottom_half[1] will hold the query, fixed length 3.
-            let bottom_half = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
-                .split(window[1]);
+            let list_block = cli.layout.block(&cli.layout.list_title, &cli.theme);
+            let prompt_block = cli.layout.block(&cli.layout.prompt_title, &cli.theme);
+
+            // Scroll the list, minus its borders and the category tabs
+            list_area = list_block.inner(areas.list);
+            let tabs_area = (cli.layout.tabs && list_area.height > 1).then(|| {
+                let tabs = Rect {
+                    height: 1,
+                    ..list_area
+                };
+                list_area.y += 1;
+                list_area.height -= 1;
+                tabs
+            });
+            let height = list_area.height as usize;
+            ui.scroll(height, cli.scrolloff);
 
             // Description of the current app.
+            let description_block = cli
+                .layout
+                .block(&cli.layout.description_title, &cli.theme)
+                .style(cli.theme.description);
             let description = Paragraph::new(ui.text.clone())
-                .block(create_block("Gyr"))
-                .style(Style::default())
+                .style(cli.theme.description)
                 // Don't trim leading spaces when wrapping
                 .wrap(Wrap { trim: false })
                 .alignment(Alignment::Left);
 
-            // Convert app list to Vec<ListItem>
+            // Convert the visible part of the app list to Vec<ListItem>, the list can be huge
             let apps = ui
-                .shown
-                .iter()
-                .map(ListItem::from)
+                .shown()
+                .enumerate()
+                .skip(ui.offset)
+                .take(height)
+                .map(|(i, app)| {
+                    // Actions listed under their app only show their own name, indented
+                    let nested = ui.nested(i);
+                    let name = match (nested, &app.action) {
+                        (true, Some(action)) => action,
+                        _ => &app.name,
+                    };
+                    let mut name = ui.highlight(name, cli.theme.matched);
+                    if let Some(glyph) = cli.glyphs.cell(app) {
+                        name.spans.insert(0, Span::raw(glyph));
+                    }
+                    if nested {
+                        name.spans.insert(0, Span::raw("  "));
+                    }
+                    // Apps with actions tell whether they're listed
+                    match ui.expanded(i) {
+                        Some(true) => name.spans.push(Span::styled(" ▾", cli.theme.border)),
+                        Some(false) => name.spans.push(Span::styled(" ▸", cli.theme.border)),
+                        None => {}
+                    }
+                    ListItem::new(name)
+                })
                 .collect::<Vec<ListItem>>();
 
             // App list (stateful widget)
             let list = List::new(apps)
-                .block(create_block("Apps"))
                 .style(Style::default())
-                // Bold & colorized selection
-                .highlight_style(
-                    Style::default()
-                        .fg(cli.highlight_color)
-                        .add_modifier(Modifier::BOLD),
-                )
+                // Bold & colorized selection, by default
+                .highlight_style(cli.theme.selected)
                 // Prefixed before the list item
                 .highlight_symbol("> ");
 
-            // Update selection
-            app_state.select(ui.selected);
+            // Update selection, relative to the visible part
+            app_state.select(
+                ui.selected
+                    .and_then(|selected| selected.checked_sub(ui.offset)),
+            );
+            *app_state.offset_mut() = 0;
 
             // Query
-            let query = Paragraph::new(Line::from(vec![
+            let mut counter = Line::from(vec![
                 // The resulting style will be:
                 // (10/51) >> filter
-                // With `10` and the first `>` colorized with the highlight color
+                // With `10` and the first `>` styled as the counter and the prompt
                 Span::raw("("),
                 Span::styled(
                     (ui.selected.map_or(0, |v| v + 1)).to_string(),
-                    Style::default().fg(cli.highlight_color),
+                    cli.theme.counter,
                 ),
                 Span::raw("/"),
-                Span::raw(ui.shown.len().to_string()),
+                Span::raw(ui.len().to_string()),
                 Span::raw(") "),
-                Span::styled(">", Style::default().fg(cli.highlight_color)),
-                Span::raw("> "),
-                Span::raw(&ui.query),
-                Span::raw(&cli.cursor),
-            ]))
-            // No title
-            .block(create_block(""))
-            .style(Style::default())
-            .alignment(Alignment::Left)
-            .wrap(ratatui::widgets::Wrap { trim: false });
-
-            // Render description
-            f.render_widget(description, window[0]);
-            // Render app list
-            f.render_stateful_widget(list, bottom_half[0], &mut app_state);
+            ]);
+
+            // Tell which matcher and vi mode are in use, when they aren't the default
+            if ui.exact {
+                counter.spans.push(Span::raw("[exact] "));
+            }
+            if mode == keymap::Mode::Normal {
+                counter.spans.push(Span::raw("[normal] "));
+            }
+            if let (false, ui::Scope::Category(category)) = (cli.layout.tabs, ui.scope().0) {
+                counter.spans.push(Span::raw(format!("[{category}] ")));
+            }
+            if ui.matching() {
+                counter.spans.push(Span::raw("[matching…] "));
+            }
+            if ui.verbose > 2 {
+                counter
+                    .spans
+                    .push(Span::raw(format!("[{:.1?}] ", ui.filter_time)));
+            }
+            counter
+                .spans
+                .extend([Span::styled(">", cli.theme.prompt), Span::raw("> ")]);
+
+            // Scroll the query horizontally, so the cursor is always visible
+            let inner = prompt_block.inner(areas.prompt);
+            let column = (counter.width() + ui.query.cursor_width()) as u16;
+            let scroll = column.saturating_sub(inner.width.saturating_sub(1));
+
+            let mut line = counter;
+            line.spans.push(Span::raw(ui.query.as_str()));
+
+            let query = Paragraph::new(line)
+                .block(prompt_block)
+                .style(Style::default())
+                .alignment(Alignment::Left)
+                .scroll((0, scroll));
+
+            // Render description, unless it's hidden
+            icon_area = None;
+            if let Some(area) = areas.description {
+                let mut text_area = description_block.inner(area);
+                f.render_widget(description_block, area);
+
+                // The icon goes in the top right corner, if there's room
+                let rows = ICON_ROWS.min(text_area.height);
+                let columns = graphics
+                    .as_ref()
+                    .map_or(rows * 2, |graphics| graphics.columns(rows));
+                if cli.icons != cli::Icons::None
+                    && rows > 0
+                    && text_area.width >= columns + 1 + ICON_MIN_TEXT
+                {
+                    let icon = Rect::new(text_area.right() - columns, text_area.y, columns, rows);
+                    text_area.width -= columns + 1;
+                    icon_area = Some(icon);
+
+                    // Without graphics, the initial of the app stands for it
+                    if let (false, Some(app)) = (drawable, ui.selected_app()) {
+                        let block = cli.layout.block("", &cli.theme);
+                        let inner = block.inner(icon);
+                        let initial = app.name.chars().flat_map(char::to_uppercase);
+                        let mut lines =
+                            vec![Line::default(); (inner.height.saturating_sub(1) / 2) as usize];
+                        lines.push(Line::from(Span::styled(
+                            initial.take(1).collect::<String>(),
+                            cli.theme.name,
+                        )));
+                        let paragraph = Paragraph::new(lines)
+                            .block(block)
+                            .alignment(Alignment::Center);
+                        f.render_widget(paragraph, icon);
+                    }
+                }
+
+                f.render_widget(description, text_area);
+            }
+            // Render app list, below the tabs
+            f.render_widget(list_block, areas.list);
+            if let Some(area) = tabs_area {
+                f.render_widget(category_tabs(&ui, &cli.theme, area.width), area);
+            }
+            f.render_stateful_widget(list, list_area, &mut app_state);
             // Render query
-            f.render_widget(query, bottom_half[1]);
+            f.render_widget(query, areas.prompt);
+            // Show the terminal cursor in the query
+            f.set_cursor(inner.x + column - scroll, inner.y);
         })?;
+        // Images aren't part of the terminal buffer, they go on top
+        if let Some(graphics) = &mut graphics {
+            graphics
+                .draw(&mut io::stdout(), icon_area.filter(|_| drawable))
+                .wrap_err("Failed to draw icon")?;
+        }
 
-        // Handle user input
-        if let Event::Input(key) = input.next()? {
-            match key {
-                // Exit on escape
-                Key::Esc | Key::Ctrl('q' | 'c') => {
-                    terminal.clear().wrap_err("Failed to clear terminal")?;
-                    ui.selected = None;
-                    break;
+        // Handle user input. While matching, wake up now and then to show the new matches
+        let event = if ui.matching() {
+            input.next_timeout(REDRAW)?
+        } else {
+            input.next()?
+        };
+        let action = match event {
+            Event::Input(key) => {
+                let Some(action) = cli.keymap.get(mode, key) else {
+                    // Unbound characters are typed into the query, in insert mode
+                    if let (keymap::Mode::Insert, Key::Char(c)) = (mode, key) {
+                        ui.query.insert(c);
+                        ui.filter();
+                        ui.info(cli.theme.name);
+                    }
+                    continue;
+                };
+                action
+            }
+            Event::Mouse(MouseEvent::Press(button, x, y)) => {
+                // Mouse coordinates are one-based
+                let row =
+                    list_row(list_area, ui.offset, x - 1, y - 1).filter(|row| *row < ui.len());
+
+                let mut double = false;
+                match (button, row) {
+                    // Scroll without wrapping around
+                    (MouseButton::WheelUp, _) => ui.move_selection(-1),
+                    (MouseButton::WheelDown, _) => ui.move_selection(1),
+                    // Select on click, run on double click. Clicking the selected app lists its
+                    // actions, or hides them
+                    (MouseButton::Left, Some(row)) => {
+                        double = last_click
+                            .is_some_and(|(last, at)| last == row && at.elapsed() < DOUBLE_CLICK);
+                        last_click = (!double).then(|| (row, Instant::now()));
+                        if !double && ui.selected == Some(row) {
+                            ui.toggle_actions();
+                        } else {
+                            ui.selected = Some(row);
+                        }
+                    }
+                    _ => {}
                 }
-                // Run app on enter
-                Key::Char('\n') | Key::Ctrl('y') => {
-                    break;
+
+                ui.info(cli.theme.name);
+                if !double {
+                    continue;
                 }
-                // Add character to query
-                Key::Char(c) => {
-                    ui.query.push(c);
-                    ui.filter();
+                // Run it below, like pressing enter
+                Action::Launch
+            }
+            // Drawn again from scratch
+            Event::Resize => {
+                if let Some(graphics) = &mut graphics {
+                    graphics.invalidate();
                 }
-                // Remove character from query
-                Key::Backspace => {
-                    ui.query.pop();
-                    ui.filter();
+                continue;
+            }
+            Event::Mouse(_) | Event::Tick => continue,
+        };
+
+        // Whether the query text changed
+        let mut changed = false;
+
+        let page = list_area.height.max(1) as isize;
+
+        match action {
+            Action::Quit => {
+                break;
+            }
+            // Run the selected app
+            Action::Launch | Action::LaunchKeepOpen => {
+                if let Some(selected) = ui.selected {
+                    let app = ui.app(selected);
+                    match launch::run(app, &cli) {
+                        Ok(()) if action == Action::Launch => {
+                            launched = Some(app.clone());
+                            break;
+                        }
+                        Ok(()) => {
+                            if cli.notify == cli::Notify::Always {
+                                notify::launched(app);
+                            }
+                            // Gyr keeps running, so a history that can't be written isn't fatal
+                            let result = index.launched(app);
+                            ui.app_mut(selected).history += 1;
+                            if let Err(error) = result {
+                                // Keep it shown, instead of the info text
+                                ui.error(&error);
+                                continue;
+                            }
+                        }
+                        // Show the error, the user can pick another app
+                        Err(error) => {
+                            if cli.notify != cli::Notify::Never {
+                                // Best-effort, the error is shown in the UI anyway
+                                notify::failed(app, &error);
+                            }
+                            if error.downcast_ref::<launch::EarlyExit>().is_some() {
+                                // The launch error is worth more than the history one
+                                index.failed(app).ok();
+                                ui.app_mut(selected).failures += 1;
+                            }
+                            ui.error(&error);
+                            continue;
+                        }
+                    }
+                }
+            }
+            // Go down one item.
+            // If we're at the bottom, back to the top.
+            Action::SelectNext => {
+                if let Some(selected) = ui.selected {
+                    ui.selected = if selected + 1 < ui.len() {
+                        Some(selected + 1)
+                    } else if !cli.hard_stop {
+                        Some(0)
+                    } else {
+                        Some(selected)
+                    };
                 }
-                // Go to top of list
-                Key::Left => {
+            }
+            // Go up one item.
+            // If we're at the top, go to the end.
+            Action::SelectPrev => {
+                if let Some(selected) = ui.selected {
+                    ui.selected = if selected > 0 {
+                        Some(selected - 1)
+                    } else if !cli.hard_stop {
+                        ui.len().checked_sub(1)
+                    } else {
+                        Some(selected)
+                    };
+                }
+            }
+            Action::SelectFirst => {
+                if ui.selected.is_some() {
                     ui.selected = Some(0);
                 }
-                // Go to end of list
-                Key::Right => {
-                    ui.selected = Some(ui.shown.len() - 1);
+            }
+            Action::SelectLast => {
+                if ui.selected.is_some() {
+                    ui.selected = ui.len().checked_sub(1);
                 }
-                // Go down one item.
-                // If we're at the bottom, back to the top.
-                Key::Down | Key::Ctrl('n') => {
-                    if let Some(selected) = ui.selected {
-                        ui.selected = if selected < ui.shown.len() - 1 {
-                            Some(selected + 1)
-                        } else if !cli.hard_stop {
-                            Some(0)
-                        } else {
-                            Some(selected)
-                        };
-                    }
+            }
+            // Move by (half) pages, as high as the list without borders
+            Action::SelectPageDown => ui.move_selection(page),
+            Action::SelectPageUp => ui.move_selection(-page),
+            Action::SelectHalfPageDown => ui.move_selection((page / 2).max(1)),
+            Action::SelectHalfPageUp => ui.move_selection(-(page / 2).max(1)),
+            // Move the cursor
+            // Past the ends of the query, list or hide the actions of the selected app
+            Action::CursorLeft if ui.query.at_start() => {
+                if ui
+                    .selected
+                    .is_some_and(|i| ui.nested(i) || ui.expanded(i) == Some(true))
+                {
+                    ui.toggle_actions();
                 }
-                // Go up one item.
-                // If we're at the top, go to the end.
-                Key::Up | Key::Ctrl('p') => {
-                    if let Some(selected) = ui.selected {
-                        ui.selected = if selected > 0 {
-                            Some(selected - 1)
-                        } else if !cli.hard_stop {
-                            Some(ui.shown.len() - 1)
-                        } else {
-                            Some(selected)
-                        };
-                    }
+            }
+            Action::CursorRight if ui.query.at_end() => {
+                if ui.selected.is_some_and(|i| ui.expanded(i) == Some(false)) {
+                    ui.toggle_actions();
                 }
-                _ => {}
             }
+            Action::CursorLeft => ui.query.left(),
+            Action::CursorRight => ui.query.right(),
+            Action::CursorStart => ui.query.home(),
+            Action::CursorEnd => ui.query.end(),
+            Action::WordLeft => ui.query.word_left(),
+            Action::WordRight => ui.query.word_right(),
+            // Edit the query
+            Action::DeleteCharBackward => changed = ui.query.backspace(),
+            Action::DeleteChar => changed = ui.query.delete(),
+            Action::KillWord => changed = ui.query.kill_word(),
+            Action::KillAlphanumericWord => changed = ui.query.kill_alphanumeric_word(),
+            Action::KillToStart => changed = ui.query.kill_to_start(),
+            Action::KillToEnd => changed = ui.query.kill_to_end(),
+            Action::Yank => changed = ui.query.yank(),
+            Action::ClearQuery => changed = ui.query.clear(),
+            Action::ToggleMatcher => {
+                ui.exact = !ui.exact;
+                changed = true;
+            }
+            Action::NextCategory => {
+                ui.cycle_category(1);
+                changed = true;
+            }
+            Action::PrevCategory => {
+                ui.cycle_category(-1);
+                changed = true;
+            }
+            Action::ToggleActions => ui.toggle_actions(),
+            // Vi mode
+            Action::NormalMode => {
+                mode = keymap::Mode::Normal;
+            }
+            Action::InsertMode => {
+                mode = keymap::Mode::Insert;
+            }
+            Action::AppendMode => {
+                ui.query.right();
+                mode = keymap::Mode::Insert;
+            }
+        }
 
-            ui.info(cli.highlight_color);
+        if changed {
+            ui.filter();
         }
+
+        ui.info(cli.theme.name);
     }
 
     // Reset terminal
-    terminal.clear().wrap_err("Failed to clear terminal")?;
-    raw_handle
-        .suspend_raw_mode()
-        .wrap_err("Failed to suspend raw stdout")?;
+    drop(guard);
 
-    if let Some(selected) = ui.selected {
-        let app_to_run = &ui.shown[selected];
-
-        // Split command in a shell-parseable format.
-        let commands = shell_words::split(&app_to_run.command)?;
-
-        // Switch to path specified by app to be run
-        if let Some(path) = &app_to_run.path {
-            env::set_current_dir(path::PathBuf::from(path)).wrap_err_with(|| {
-                format!("Failed to switch to {path} when starting {app_to_run}")
-            })?;
+    if let Some(app) = launched {
+        if cli.notify == cli::Notify::Always {
+            // Best-effort, there's nowhere left to show the error
+            notify::launched(&app).wait().ok();
         }
-
-        // Actual commands being run
-        let mut runner: Vec<&str> = vec![];
-
-        // Use `swaymsg` to run the command.
-        // Allows Sway to move the app to the workspace Gyr was run in.
-        if cli.sway {
-            runner.extend_from_slice(&["swaymsg", "exec", "--"]);
+        match index.launched(&app) {
+            // The app runs anyway
+            Err(error) if matches!(index, Index::Fallback(_)) => eprintln!("Warning: {error}"),
+            result => result?,
         }
+    }
 
-        // Use terminal runner to run the app.
-        if app_to_run.is_terminal {
-            runner.extend_from_slice(&cli.terminal_launcher.split(' ').collect::<Vec<&str>>());
-        }
+    Ok(())
+}
 
-        // Add app commands
-        runner.extend_from_slice(&commands.iter().map(AsRef::as_ref).collect::<Vec<&str>>());
-
-        let mut exec = process::Command::new(runner[0]);
-        exec.args(&runner[1..]);
-
-        // Set program as session leader.
-        // Otherwise the OS may kill the app after the Gyr exits.
-        //
-        // # Safety: pre_exec() isn't modifyng the memory and setsid() fails if the calling
-        // process is already a process group leader (which isn't)
-        #[allow(unsafe_code)]
-        unsafe {
-            exec.pre_exec(|| {
-                libc::setsid();
-                Ok(())
-            });
-        }
+/// Lines at the bottom of the terminal for `height` lines, below the cursor
+///
+/// ratatui's inline viewport makes room for them, scrolling the screen up if needed. It asks the
+/// terminal where the cursor is, so this has to run before [Input] reads stdin. Once placed, the
+/// viewport is fixed: an inline one asks again on every resize.
+fn inline_area(height: layout::Size) -> io::Result<Rect> {
+    let (_, rows) = termion::terminal_size()?;
+    let lines = height.of(rows).max(1);
+    let mut terminal = Terminal::with_options(
+        TermionBackend::new(io::stdout()),
+        TerminalOptions {
+            viewport: Viewport::Inline(lines),
+        },
+    )?;
+    Ok(terminal.get_frame().size())
+}
 
-        if cli.verbose.unwrap_or(0) > 0 {
-            exec.stdin(process::Stdio::null())
-                .stdout(process::Stdio::null())
-                .stderr(process::Stdio::null())
-                .spawn()
-                .wrap_err_with(|| format!("Failed to run {exec:?}"))?;
-        } else {
-            exec.spawn()
-                .wrap_err_with(|| format!("Failed to run {exec:?}"))?;
-        }
+/// Terminal that only draws in `area`
+fn inline_terminal(area: Rect) -> io::Result<Terminal<Backend>> {
+    Terminal::with_options(
+        TermionBackend::new(io::stdout()),
+        TerminalOptions {
+            viewport: Viewport::Fixed(area),
+        },
+    )
+}
 
-        {
-            let value = app_to_run.history + 1;
-            let packed = bytes::pack(value);
-            db.insert(app_to_run.name.as_bytes(), &packed).unwrap();
+/// Category tabs, with the number of apps matching in each. The first ones are left out if the
+/// current one doesn't fit in `width`
+fn category_tabs(ui: &UI, theme: &theme::Theme, width: u16) -> Tabs<'static> {
+    let tabs = ui.tabs();
+    let current = match ui.scope().0 {
+        ui::Scope::All => Some(0),
+        ui::Scope::Category(category) => tabs.iter().position(|(c, _)| *c == Some(category)),
+        ui::Scope::Unknown => None,
+    };
+    let titles = tabs
+        .iter()
+        .map(|(category, count)| {
+            let name = category.map_or("All", xdg::Category::name);
+            format!("{name} {count}")
+        })
+        .collect::<Vec<_>>();
+
+    // Each tab is padded by a space on each side, and followed by a divider
+    let mut first = 0;
+    if let Some(current) = current {
+        let fits = |first: usize| {
+            // Names and counts are ASCII
+            let tabs = titles[first..=current].iter().map(|t| t.len() + 3);
+            tabs.sum::<usize>() <= width as usize
+        };
+        while first < current && !fits(first) {
+            first += 1;
         }
     }
 
-    fs::remove_file(lock_path).wrap_err("Failed to remove lock file")?;
+    Tabs::new(titles[first..].to_vec())
+        .select(current.map_or(usize::MAX, |current| current - first))
+        .highlight_style(theme.selected)
+        .divider(Span::styled("│", theme.border))
+}
 
-    Ok(())
+/// Index of the list item at `x`, `y` (zero-based), if they're inside `area` (the list, without
+/// its borders), scrolled down to `offset`
+fn list_row(area: Rect, offset: usize, x: u16, y: u16) -> Option<usize> {
+    let inside = x >= area.x && x < area.right() && y >= area.y && y < area.bottom();
+    inside.then(|| offset + (y - area.y) as usize)
 }
 
-/// Byte packer and unpacker
-mod bytes {
-    /// Unacks an `[u8; 8]` array into a single `u64`, previously packed with [pack]
-    ///
-    /// [pack]: pack
-    pub const fn unpack(buffer: [u8; 8]) -> u64 {
-        let mut data = 0u64;
-        data |= buffer[0] as u64;
-        data |= (buffer[1] as u64) << 8;
-        data |= (buffer[2] as u64) << 16;
-        data |= (buffer[3] as u64) << 24;
-        data |= (buffer[4] as u64) << 32;
-        data |= (buffer[5] as u64) << 40;
-        data |= (buffer[6] as u64) << 48;
-        data |= (buffer[7] as u64) << 56;
-        data
+/// Where apps and launch history come from
+enum Index {
+    /// Read desktop files and the history database directly
+    Local(sled::Db),
+    /// Ask a running daemon, which keeps both in memory
+    Daemon(daemon::Client),
+    /// The daemon didn't answer, so desktop files are read directly into an empty, temporary
+    /// history database. The daemon keeps the real one
+    Fallback(sled::Db),
+}
+
+impl Index {
+    /// Records that `app` was run
+    fn launched(&mut self, app: &xdg::App) -> eyre::Result<()> {
+        match self {
+            Self::Local(db) => xdg::AppHistory::new(db)
+                .and_then(|history| history.launched(app))
+                .map(drop)
+                .wrap_err("Failed to update history"),
+            Self::Daemon(client) => client.launched(app),
+            Self::Fallback(_) => Err(eyre!(
+                "The daemon didn't answer, {} wasn't added to the history",
+                app.name
+            )),
+        }
     }
 
-    /// Packs an `u64` into a `[u8; 8]` array.
-    ///
-    /// Can be unpacked with [unpack].
-    ///
-    /// [unpack]: unpack
-    pub const fn pack(data: u64) -> [u8; 8] {
-        let mut buffer = [0u8; 8];
-        buffer[0] = (data & 0xFF) as u8;
-        buffer[1] = ((data >> 8) & 0xFF) as u8;
-        buffer[2] = ((data >> 16) & 0xFF) as u8;
-        buffer[3] = ((data >> 24) & 0xFF) as u8;
-        buffer[4] = ((data >> 32) & 0xFF) as u8;
-        buffer[5] = ((data >> 40) & 0xFF) as u8;
-        buffer[6] = ((data >> 48) & 0xFF) as u8;
-        buffer[7] = ((data >> 56) & 0xFF) as u8;
-        buffer
+    /// Records that `app` failed to start
+    fn failed(&mut self, app: &xdg::App) -> eyre::Result<()> {
+        match self {
+            Self::Local(db) => xdg::AppHistory::new(db)
+                .and_then(|history| history.failed(app))
+                .map(drop)
+                .wrap_err("Failed to update history"),
+            Self::Daemon(client) => client.failed(app),
+            Self::Fallback(_) => Err(eyre!(
+                "The daemon didn't answer, the failure of {} wasn't added to the history",
+                app.name
+            )),
+        }
     }
 }
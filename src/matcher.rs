@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+/// How often the worker sends the matches it found so far
+const BATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Names checked between looks at the generation, to notice cancellation
+const CHUNK: usize = 512;
+
+/// Something to match
+struct Job {
+    generation: usize,
+    /// Names of every app, indexed like the app store
+    names: Arc<[String]>,
+    query: String,
+    exact: bool,
+    /// Indices of the names to match, in order. All of them if `None`
+    candidates: Option<Vec<usize>>,
+}
+
+/// Matches found by the worker
+pub struct Batch {
+    /// Job these matches belong to
+    pub generation: usize,
+    /// Indices of the matching names, and their scores
+    pub matches: Vec<(usize, i64)>,
+    /// Whether this is the last batch of the job
+    pub done: bool,
+}
+
+/// Matches app names on a background thread
+///
+/// Starting a job cancels the previous one. Matches come back in batches, so the list can be
+/// drawn before matching is done.
+pub struct Matcher {
+    jobs: mpsc::Sender<Job>,
+    results: mpsc::Receiver<Batch>,
+    /// Current job. The worker gives up on a job as soon as this changes
+    generation: Arc<AtomicUsize>,
+    _handle: thread::JoinHandle<()>,
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Matcher {
+    /// Starts the worker thread
+    pub fn new() -> Self {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let (tx, results) = mpsc::channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        let _handle = {
+            let generation = generation.clone();
+            thread::spawn(move || {
+                let matcher = SkimMatcherV2::default();
+                while let Ok(mut job) = rx.recv() {
+                    // Only the last job matters
+                    while let Ok(newer) = rx.try_recv() {
+                        job = newer;
+                    }
+                    if run(&matcher, &job, &generation, &tx).is_err() {
+                        // The UI is gone
+                        return;
+                    }
+                }
+            })
+        };
+
+        Self {
+            jobs,
+            results,
+            generation,
+            _handle,
+        }
+    }
+
+    /// Matches `query` against `names`, or only the ones in `candidates`, cancelling the
+    /// current job
+    ///
+    /// Returns the generation of the new job, its batches are tagged with it
+    pub fn start(
+        &self,
+        names: Arc<[String]>,
+        query: &str,
+        exact: bool,
+        candidates: Option<Vec<usize>>,
+    ) -> usize {
+        let generation = self.cancel();
+        // If the worker is gone, `recv` tells the caller there's nothing coming
+        self.jobs
+            .send(Job {
+                generation,
+                names,
+                query: query.to_string(),
+                exact,
+                candidates,
+            })
+            .ok();
+        generation
+    }
+
+    /// Cancels the current job. Returns the generation that no job has yet
+    pub fn cancel(&self) -> usize {
+        self.generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Waits for the next batch, until `deadline`, or forever if it's `None`
+    pub fn recv(&self, deadline: Option<Instant>) -> Option<Batch> {
+        if let Ok(batch) = self.results.try_recv() {
+            return Some(batch);
+        }
+        match deadline {
+            Some(deadline) => self
+                .results
+                .recv_timeout(deadline.checked_duration_since(Instant::now())?)
+                .ok(),
+            None => self.results.recv().ok(),
+        }
+    }
+}
+
+/// Runs `job`, sending its matches every [`BATCH_INTERVAL`]. Stops early if it's cancelled
+fn run(
+    matcher: &SkimMatcherV2,
+    job: &Job,
+    generation: &AtomicUsize,
+    tx: &mpsc::Sender<Batch>,
+) -> Result<(), mpsc::SendError<Batch>> {
+    let lowercase = job.query.to_lowercase();
+    let score = |name: &str| {
+        if !job.exact {
+            return matcher.fuzzy_match(name, &job.query);
+        }
+        if lowercase.is_empty() {
+            return Some(0);
+        }
+
+        // Exact matches score higher the closer they are to the start of `name`
+        let position = name.to_lowercase().find(&lowercase)?;
+        Some(100 - position.min(99) as i64)
+    };
+
+    let all = 0..job.names.len();
+    let mut candidates: Box<dyn Iterator<Item = usize>> = match &job.candidates {
+        Some(candidates) => Box::new(candidates.iter().copied()),
+        None => Box::new(all),
+    };
+
+    let mut matches = vec![];
+    let mut sent = Instant::now();
+    loop {
+        if generation.load(Ordering::Relaxed) != job.generation {
+            return Ok(());
+        }
+
+        let mut done = true;
+        for i in candidates.by_ref().take(CHUNK) {
+            done = false;
+            if let Some(score) = score(&job.names[i]) {
+                matches.push((i, score));
+            }
+        }
+
+        if done || sent.elapsed() >= BATCH_INTERVAL {
+            tx.send(Batch {
+                generation: job.generation,
+                matches: std::mem::take(&mut matches),
+                done,
+            })?;
+            sent = Instant::now();
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
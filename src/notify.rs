@@ -0,0 +1,91 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use eyre::{eyre, WrapErr};
+use notify_rust::{Notification, Urgency};
+
+use super::xdg::App;
+
+/// How long to wait for a notification to be sent before exiting
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A notification being sent in the background
+///
+/// The bus can be slow, or have no notification server at all, so sending never blocks the
+/// caller. Dropping it leaves the notification to its thread.
+pub struct Pending(mpsc::Receiver<eyre::Result<()>>);
+
+impl Pending {
+    /// Waits a bit for the notification to be sent
+    ///
+    /// Needed right before exiting, which would kill the thread sending it
+    pub fn wait(self) -> eyre::Result<()> {
+        self.0
+            .recv_timeout(TIMEOUT)
+            .map_err(|_| eyre!("Timed out sending notification"))?
+    }
+}
+
+/// Sends a notification saying `app` was launched
+pub fn launched(app: &App) -> Pending {
+    send(
+        app,
+        format!("Launched {}", app.name),
+        &app.description,
+        Urgency::Low,
+    )
+}
+
+/// Sends a notification saying `app` failed to launch, with the `error` causes in the body
+pub fn failed(app: &App, error: &eyre::Report) -> Pending {
+    let body = error
+        .chain()
+        .skip(1)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    send(app, error.to_string(), &body, Urgency::Critical)
+}
+
+/// Calls `org.freedesktop.Notifications.Notify` on the session bus, from another thread
+fn send(app: &App, summary: String, body: &str, urgency: Urgency) -> Pending {
+    let mut notification = Notification::new();
+    notification
+        .appname(env!("CARGO_PKG_NAME"))
+        .icon(app.icon.as_deref().unwrap_or_default())
+        .summary(&summary)
+        // Some servers render the body as markup
+        .body(&escape(body))
+        .urgency(urgency);
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = notification
+            .show()
+            .map(drop)
+            .wrap_err("Failed to send notification");
+        // Nobody's waiting for it anymore
+        sender.send(result).ok();
+    });
+    Pending(receiver)
+}
+
+/// Escapes the characters that have a meaning in the notification body markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markup() {
+        assert_eq!(escape("Web & more <b>"), "Web &amp; more &lt;b&gt;");
+        assert_eq!(escape("plain"), "plain");
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::fd::FromRawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// Set when a termination signal is received
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Write end of the pipe [resizes] reads from, -1 until it's created
+static RESIZE_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn handler(_signal: libc::c_int) {
+    RECEIVED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn resize_handler(_signal: libc::c_int) {
+    let fd = RESIZE_PIPE.load(Ordering::SeqCst);
+    // Safety: write is async-signal-safe. The write end doesn't block: if the pipe is full, it
+    // fails, but there's a resize pending anyway
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::write(fd, [0u8].as_ptr().cast(), 1);
+    }
+}
+
+/// Sets `handler` as the handler of `signal`
+fn set_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) -> io::Result<()> {
+    // Safety: sigaction is a plain C struct, and the handlers only use async-signal-safe
+    // operations
+    #[allow(unsafe_code)]
+    let ret = unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, ptr::null_mut())
+    };
+
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Installs handlers for SIGTERM, SIGINT and SIGHUP.
+///
+/// Instead of dying, Gyr notices with [received] and quits cleanly: restoring the terminal and
+/// removing its lock.
+pub fn install() -> io::Result<()> {
+    for signal in [libc::SIGTERM, libc::SIGINT, libc::SIGHUP] {
+        set_handler(signal, handler)?;
+    }
+
+    Ok(())
+}
+
+/// Handles SIGWINCH, returning a pipe that gets a byte each time the terminal is resized.
+///
+/// Signal handlers can't do much, so instead of telling the UI directly, the handler writes to
+/// the pipe, and a thread reading from it does.
+pub fn resizes() -> io::Result<fs::File> {
+    let mut fds = [0; 2];
+    // Safety: pipe2 fills both fds, the read end is owned by the returned file and the write end
+    // lives as long as Gyr, for the handler. Neither is inherited by launched apps, and only the
+    // write end is non-blocking, the handler can't wait for the reader
+    #[allow(unsafe_code)]
+    let read = unsafe {
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let read = fs::File::from_raw_fd(fds[0]);
+        if libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) != 0 {
+            let error = io::Error::last_os_error();
+            libc::close(fds[1]);
+            return Err(error);
+        }
+        read
+    };
+
+    // Only one pipe, the first one
+    if RESIZE_PIPE
+        .compare_exchange(-1, fds[1], Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "already watching resizes",
+        ));
+    }
+    set_handler(libc::SIGWINCH, resize_handler)?;
+
+    Ok(read)
+}
+
+/// Asks Gyr to quit, as if it received a termination signal
+pub fn request_quit() {
+    RECEIVED.store(true, Ordering::SeqCst);
+}
+
+/// Whether a termination signal was received, or [request_quit] was called
+pub fn received() -> bool {
+    RECEIVED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,351 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+/// Element names, as written in the config file
+///
+/// Gyr has no pinned apps, so there's no pinned marker to style. It gets an element when they're
+/// added.
+const ELEMENTS: &[&str] = &[
+    "border",
+    "title",
+    "selected",
+    "match",
+    "name",
+    "description",
+    "prompt",
+    "counter",
+];
+
+/// Bundled themes
+const THEMES: &[&str] = &["default", "monochrome", "nord", "gruvbox"];
+
+/// Modifier names, as written in the config file
+const MODIFIERS: &[(&str, Modifier)] = &[
+    ("bold", Modifier::BOLD),
+    ("dim", Modifier::DIM),
+    ("italic", Modifier::ITALIC),
+    ("underlined", Modifier::UNDERLINED),
+    ("underline", Modifier::UNDERLINED),
+    ("reversed", Modifier::REVERSED),
+    ("crossed_out", Modifier::CROSSED_OUT),
+];
+
+/// Style of an element, as written in the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StyleConf {
+    /// Foreground color
+    pub fg: Option<String>,
+    /// Background color
+    pub bg: Option<String>,
+    /// Modifier names, like `bold`
+    pub modifiers: Option<Vec<String>>,
+}
+
+/// Styles of each UI element
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    /// Borders of the description, the list and the query
+    pub border: Style,
+    /// Titles of the description and the list
+    pub title: Style,
+    /// Selected app in the list
+    pub selected: Style,
+    /// Characters of the app names matching the query
+    pub matched: Style,
+    /// Name of the selected app, above its description
+    pub name: Style,
+    /// Description of the selected app
+    pub description: Style,
+    /// First `>` of the query prompt
+    pub prompt: Style,
+    /// Position of the selected app, in the counter before the query
+    pub counter: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::named("default").expect("bundled theme")
+    }
+}
+
+impl Theme {
+    /// Bundled theme called `name`
+    pub fn named(name: &str) -> Result<Self, String> {
+        let fg = |color| Style::default().fg(color);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+
+        let theme = match name {
+            "default" => Self {
+                border: Style::default(),
+                title: bold,
+                selected: fg(Color::LightBlue).add_modifier(Modifier::BOLD),
+                matched: fg(Color::LightBlue),
+                name: fg(Color::LightBlue),
+                description: Style::default(),
+                prompt: fg(Color::LightBlue),
+                counter: fg(Color::LightBlue),
+            },
+            "monochrome" => Self {
+                border: Style::default(),
+                title: bold,
+                selected: bold.add_modifier(Modifier::REVERSED),
+                matched: Style::default().add_modifier(Modifier::UNDERLINED),
+                name: bold,
+                description: Style::default(),
+                prompt: bold,
+                counter: bold,
+            },
+            "nord" => Self {
+                border: fg(Color::Rgb(0x4c, 0x56, 0x6a)),
+                title: fg(Color::Rgb(0x88, 0xc0, 0xd0)).add_modifier(Modifier::BOLD),
+                selected: fg(Color::Rgb(0xec, 0xef, 0xf4))
+                    .bg(Color::Rgb(0x3b, 0x42, 0x52))
+                    .add_modifier(Modifier::BOLD),
+                matched: fg(Color::Rgb(0x88, 0xc0, 0xd0)),
+                name: fg(Color::Rgb(0x88, 0xc0, 0xd0)).add_modifier(Modifier::BOLD),
+                description: fg(Color::Rgb(0xd8, 0xde, 0xe9)),
+                prompt: fg(Color::Rgb(0x81, 0xa1, 0xc1)),
+                counter: fg(Color::Rgb(0xeb, 0xcb, 0x8b)),
+            },
+            "gruvbox" => Self {
+                border: fg(Color::Rgb(0x66, 0x5c, 0x54)),
+                title: fg(Color::Rgb(0xfa, 0xbd, 0x2f)).add_modifier(Modifier::BOLD),
+                selected: fg(Color::Rgb(0xfb, 0xf1, 0xc7))
+                    .bg(Color::Rgb(0x3c, 0x38, 0x36))
+                    .add_modifier(Modifier::BOLD),
+                matched: fg(Color::Rgb(0xfe, 0x80, 0x19)),
+                name: fg(Color::Rgb(0xfa, 0xbd, 0x2f)).add_modifier(Modifier::BOLD),
+                description: fg(Color::Rgb(0xeb, 0xdb, 0xb2)),
+                prompt: fg(Color::Rgb(0xb8, 0xbb, 0x26)),
+                counter: fg(Color::Rgb(0x83, 0xa5, 0x98)),
+            },
+            _ => {
+                return Err(format!(
+                    "unknown theme \"{name}\", expected one of: {}",
+                    THEMES.join(", ")
+                ))
+            }
+        };
+
+        Ok(theme)
+    }
+
+    /// Colors every highlighted element with `color`, like `highlight_color` always did
+    pub fn highlight(&mut self, color: Color) {
+        for style in [
+            &mut self.selected,
+            &mut self.matched,
+            &mut self.name,
+            &mut self.prompt,
+            &mut self.counter,
+        ] {
+            *style = style.fg(color);
+        }
+    }
+
+    /// Replaces the style of the element named `element`
+    pub fn set(&mut self, element: &str, conf: &StyleConf) -> Result<(), String> {
+        let style = match element {
+            "border" => &mut self.border,
+            "title" => &mut self.title,
+            "selected" => &mut self.selected,
+            "match" => &mut self.matched,
+            "name" => &mut self.name,
+            "description" => &mut self.description,
+            "prompt" => &mut self.prompt,
+            "counter" => &mut self.counter,
+            _ => {
+                return Err(format!(
+                    "unknown element \"{element}\", expected one of: {}",
+                    ELEMENTS.join(", ")
+                ))
+            }
+        };
+
+        let mut new = Style::default();
+        if let Some(fg) = &conf.fg {
+            new = new.fg(parse_color(fg).map_err(|e| format!("{element}: {e}"))?);
+        }
+        if let Some(bg) = &conf.bg {
+            new = new.bg(parse_color(bg).map_err(|e| format!("{element}: {e}"))?);
+        }
+        for name in conf.modifiers.iter().flatten() {
+            let Some((_, modifier)) = MODIFIERS.iter().find(|(m, _)| m == name) else {
+                let names = MODIFIERS.iter().map(|(m, _)| *m).collect::<Vec<_>>();
+                return Err(format!(
+                    "{element}: unknown modifier \"{name}\", expected one of: {}",
+                    names.join(", ")
+                ));
+            };
+            new = new.add_modifier(*modifier);
+        }
+
+        *style = new;
+        Ok(())
+    }
+}
+
+/// Parses a color: one of the 16 ANSI color names, `reset`, `#rrggbb`, `rgb(r, g, b)` or a
+/// 256-color palette index
+///
+/// Names are case-insensitive
+pub fn parse_color(val: &str) -> Result<Color, String> {
+    let lowercase = val.trim().to_lowercase();
+    let err = || {
+        format!(
+            "invalid color \"{val}\", expected a color name, #rrggbb, rgb(r, g, b) or a number \
+             from 0 to 255"
+        )
+    };
+
+    let color = match lowercase.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') => {
+            let hex = &hex[1..];
+            if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(err());
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+            Color::Rgb(channel(0)?, channel(2)?, channel(4)?)
+        }
+        rgb if rgb.starts_with("rgb(") && rgb.ends_with(')') => {
+            let channels = rgb[4..rgb.len() - 1]
+                .split(',')
+                .map(|channel| channel.trim().parse::<u8>().map_err(|_| err()))
+                .collect::<Result<Vec<_>, _>>()?;
+            match channels[..] {
+                [r, g, b] => Color::Rgb(r, g, b),
+                _ => return Err(err()),
+            }
+        }
+        index => Color::Indexed(index.parse().map_err(|_| err())?),
+    };
+
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("LightBlue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color(" DarkGray "), Ok(Color::DarkGray));
+        assert_eq!(parse_color("reset"), Ok(Color::Reset));
+        assert_eq!(parse_color("default"), Ok(Color::Reset));
+        assert!(parse_color("purple").is_err());
+        assert!(parse_color("").is_err());
+    }
+
+    #[test]
+    fn parse_rgb() {
+        assert_eq!(parse_color("#88c0d0"), Ok(Color::Rgb(0x88, 0xc0, 0xd0)));
+        assert_eq!(parse_color("#88C0D0"), Ok(Color::Rgb(0x88, 0xc0, 0xd0)));
+        assert_eq!(parse_color("rgb(1, 2, 3)"), Ok(Color::Rgb(1, 2, 3)));
+        assert_eq!(parse_color("RGB(1,2,3)"), Ok(Color::Rgb(1, 2, 3)));
+        for invalid in [
+            "#88c0d",
+            "#88c0d0ff",
+            "#+8c0d0",
+            "#",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4)",
+            "rgb(1, 2, 256)",
+            "rgb(1, 2, 3",
+        ] {
+            assert!(parse_color(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn parse_index() {
+        assert_eq!(parse_color("0"), Ok(Color::Indexed(0)));
+        assert_eq!(parse_color("255"), Ok(Color::Indexed(255)));
+        assert!(parse_color("256").is_err());
+        assert!(parse_color("-1").is_err());
+    }
+
+    #[test]
+    fn bundled() {
+        for name in THEMES {
+            assert!(Theme::named(name).is_ok(), "{name}");
+        }
+        assert!(Theme::named("solarized").is_err());
+    }
+
+    #[test]
+    fn set() {
+        let mut theme = Theme::default();
+        let conf = StyleConf {
+            fg: Some("red".to_string()),
+            bg: Some("#000000".to_string()),
+            modifiers: Some(vec!["italic".to_string(), "underline".to_string()]),
+        };
+        theme.set("match", &conf).unwrap();
+        assert_eq!(
+            theme.matched,
+            Style::default()
+                .fg(Color::Red)
+                .bg(Color::Rgb(0, 0, 0))
+                .add_modifier(Modifier::ITALIC | Modifier::UNDERLINED)
+        );
+
+        // The whole style is replaced, not merged
+        theme.set("selected", &StyleConf::default()).unwrap();
+        assert_eq!(theme.selected, Style::default());
+    }
+
+    #[test]
+    fn set_every_element() {
+        let mut theme = Theme::default();
+        for element in ELEMENTS {
+            assert!(
+                theme.set(element, &StyleConf::default()).is_ok(),
+                "{element}"
+            );
+        }
+    }
+
+    #[test]
+    fn set_invalid() {
+        let mut theme = Theme::default();
+        let error = theme.set("pinned", &StyleConf::default()).unwrap_err();
+        assert!(error.starts_with("unknown element \"pinned\""), "{error}");
+
+        let conf = StyleConf {
+            fg: Some("purple".to_string()),
+            ..StyleConf::default()
+        };
+        let error = theme.set("name", &conf).unwrap_err();
+        assert!(error.starts_with("name: invalid color"), "{error}");
+
+        let conf = StyleConf {
+            modifiers: Some(vec!["blink".to_string()]),
+            ..StyleConf::default()
+        };
+        let error = theme.set("name", &conf).unwrap_err();
+        assert!(error.starts_with("name: unknown modifier"), "{error}");
+
+        // Nothing changed
+        assert_eq!(theme.name, Theme::default().name);
+    }
+}
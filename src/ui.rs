@@ -1,26 +1,114 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::matcher::Matcher;
+use super::xdg::{self, Category};
+
+/// How long [`UI::filter`] waits for the matcher, before showing partial results
+const WAIT: Duration = Duration::from_millis(10);
+
+/// How long [`UI::replace`] waits for the matcher, the rest of the matches come from [`UI::poll`]
+const REPLACE_WAIT: Duration = Duration::from_millis(500);
+
+/// Query prefix restricting the list to a category, like `cat:graphics`
+const CATEGORY_PREFIX: &str = "cat:";
 
-use super::xdg;
+/// Apps the list is restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Every app
+    All,
+    /// Apps in this category
+    Category(Category),
+    /// None, the `cat:` prefix doesn't name a category
+    Unknown,
+}
+
+impl Scope {
+    /// Whether `app` is in scope
+    fn contains(self, app: &xdg::App) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Category(category) => app.main_categories.contains(&category),
+            Scope::Unknown => false,
+        }
+    }
+}
 
 /// Application filtering and sorting facility
+///
+/// Every app lives in a single store, the list is a vector of indices into it. Results are
+/// cached per query, so typing only scores the previous matches, and deleting restores the
+/// previous results without matching at all.
+///
+/// Matching runs in the background, see [`UI::poll`].
 pub struct UI<'a> {
-    /// Hidden apps (They don't match the current query)
-    pub hidden: Vec<xdg::App>,
-    /// Shown apps (They match the current query)
-    pub shown: Vec<xdg::App>,
-    /// Current selection (index of `self.shown`)
+    /// Every app, matching the query or not
+    apps: Vec<xdg::App>,
+    /// Apps matching the current query (indices of `self.apps`) in the current category, sorted,
+    /// with the actions of the expanded app after it
+    shown: Vec<usize>,
+    /// Where the actions of the expanded app are in `self.shown`
+    nested: Range<usize>,
+    /// Desktop actions of each app, rebuilt with `self.names`
+    tree: Tree,
+    /// Apps matching the current query in any category
+    matched: usize,
+    /// Apps matching the current query in each category, indexed like [`Category::ALL`]
+    counts: [usize; Category::ALL.len()],
+    /// Categories with any app, rebuilt with `self.names`
+    categories: Vec<Category>,
+    /// Sorted results for the current query and its prefixes, each query being a prefix of the
+    /// next
+    cache: Vec<Filtered>,
+    /// Whether the cached results were matched as a substring
+    cache_exact: bool,
+    /// Names of every app, for the matcher. Rebuilt when the store changes
+    names: Arc<[String]>,
+    /// Position of each app when sorted by name, rebuilt with `self.names`
+    ranks: Vec<usize>,
+    /// Whether `self.names` is out of date
+    names_dirty: bool,
+    /// Matching in progress
+    matching: Option<Matching>,
+    /// Current selection (index of the shown apps)
     pub selected: Option<usize>,
+    /// Index of the first app shown in the list
+    pub offset: usize,
     /// Info text
     pub text: Vec<Line<'a>>,
+    /// Whether `text` is an error, until the selection changes
+    pub failed: bool,
     /// User query (used for matching)
-    pub query: String,
+    pub query: Query,
     /// Verbosity level
     pub verbose: u64,
+    /// Match the query as a substring instead of fuzzy matching
+    pub exact: bool,
+    /// Category tab the list is restricted to, unless the query has a `cat:` prefix
+    pub category: Option<Category>,
+    /// Show the desktop actions matching the query next to the apps, not only under their app
+    pub search_actions: bool,
+    /// Keep the selected app selected while it matches the query, instead of going back to the
+    /// top every time the query changes
+    pub sticky: bool,
+    /// How long the last [filter](Self::filter) took
+    pub filter_time: Duration,
+    #[doc(hidden)]
+    // Matching algorithm, on its own thread
+    matcher: Matcher,
     #[doc(hidden)]
-    // Matching algorithm
-    matcher: SkimMatcherV2,
+    // Same algorithm, to find the matching characters of the visible apps
+    highlighter: SkimMatcherV2,
 }
 
 impl<'a> UI<'a> {
@@ -30,14 +118,172 @@ impl<'a> UI<'a> {
     /// [Apps]: `super::xdg::App`
     pub fn new(items: Vec<xdg::App>) -> UI<'a> {
         UI {
+            apps: items,
             shown: vec![],
-            hidden: items,
-            selected: Some(0),
+            nested: 0..0,
+            tree: Tree::default(),
+            matched: 0,
+            counts: [0; Category::ALL.len()],
+            categories: vec![],
+            cache: vec![],
+            cache_exact: false,
+            names: Arc::new([]),
+            ranks: vec![],
+            names_dirty: true,
+            matching: None,
+            selected: None,
+            offset: 0,
             text: vec![],
-            query: String::new(),
+            failed: false,
+            query: Query::default(),
             verbose: 0,
-            matcher: SkimMatcherV2::default(),
+            exact: false,
+            category: None,
+            search_actions: false,
+            sticky: false,
+            filter_time: Duration::ZERO,
+            matcher: Matcher::new(),
+            highlighter: SkimMatcherV2::default(),
+        }
+    }
+
+    /// Number of shown apps
+    pub fn len(&self) -> usize {
+        self.shown.len()
+    }
+
+    /// Whether no apps are shown
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    /// Shown apps, in order
+    pub fn shown(&self) -> impl Iterator<Item = &xdg::App> {
+        self.shown.iter().map(|i| &self.apps[*i])
+    }
+
+    /// The `i`th shown app
+    pub fn app(&self, i: usize) -> &xdg::App {
+        &self.apps[self.shown[i]]
+    }
+
+    /// Whether the `i`th shown app is an action listed under its app
+    pub fn nested(&self, i: usize) -> bool {
+        self.nested.contains(&i)
+    }
+
+    /// Whether the `i`th shown app has actions and, if so, whether they're listed under it
+    pub fn expanded(&self, i: usize) -> Option<bool> {
+        let app = self.shown[i];
+        self.tree
+            .children
+            .contains_key(&app)
+            .then_some(self.tree.expanded == Some(app))
+    }
+
+    /// Lists the actions of the selected app under it, or hides them if they're listed. Works
+    /// from one of its actions too, selecting the app
+    pub fn toggle_actions(&mut self) {
+        let Some(&selected) = self.selected.and_then(|i| self.shown.get(i)) else {
+            return;
+        };
+        let app = self.tree.parents.get(selected).copied().flatten();
+        let app = app.unwrap_or(selected);
+        if !self.tree.children.contains_key(&app) {
+            return;
         }
+        self.tree.expanded = (self.tree.expanded != Some(app)).then_some(app);
+
+        // Show the same matches again, unless they're gone
+        let (scope, query) = scope(self.query.as_str(), self.category);
+        let search_actions = self.search_actions && !query.is_empty();
+        let matches = match (&self.matching, self.cache.last()) {
+            (Some(matching), _) => &matching.matches,
+            (None, Some(filtered)) => &filtered.shown,
+            (None, None) => {
+                self.refilter(Some(app));
+                return;
+            }
+        };
+        let (shown, counts) = (&mut self.shown, &mut self.counts);
+        let tree = &self.tree;
+        (self.matched, self.nested) = show(
+            &self.apps,
+            tree,
+            scope,
+            search_actions,
+            matches,
+            shown,
+            counts,
+        );
+        // Stay on the action if its app isn't shown
+        let app = if self.shown.contains(&app) {
+            app
+        } else {
+            selected
+        };
+        self.select(Some(app));
+    }
+
+    /// The selected app, if any is shown
+    pub fn selected_app(&self) -> Option<&xdg::App> {
+        self.shown.get(self.selected?).map(|i| &self.apps[*i])
+    }
+
+    /// The `i`th shown app, mutably
+    ///
+    /// Changing its history changes the order, so the cached results are dropped
+    pub fn app_mut(&mut self, i: usize) -> &mut xdg::App {
+        self.cache.clear();
+        &mut self.apps[self.shown[i]]
+    }
+
+    /// Adds an app, hidden until the next [filter](Self::filter)
+    pub fn add(&mut self, app: xdg::App) {
+        self.apps.push(app);
+        self.names_dirty = true;
+        self.cache.clear();
+        // It wouldn't be matched
+        self.cancel();
+    }
+
+    /// Apps the list is restricted to, and the query without its `cat:` prefix
+    ///
+    /// The prefix names a category by the start of its name, ignoring case. It wins over
+    /// [`UI::category`]
+    pub fn scope(&self) -> (Scope, &str) {
+        scope(self.query.as_str(), self.category)
+    }
+
+    /// Category tabs: every app, then each category with any app, with how many of them match
+    /// the query
+    pub fn tabs(&self) -> Vec<(Option<Category>, usize)> {
+        let categories = self
+            .categories
+            .iter()
+            .map(|category| (Some(*category), self.counts[*category as usize]));
+        [(None, self.matched)]
+            .into_iter()
+            .chain(categories)
+            .collect()
+    }
+
+    /// Moves the category tab `delta` tabs right (or left, if negative), wrapping around
+    ///
+    /// Filter again to show its apps
+    pub fn cycle_category(&mut self, delta: isize) {
+        let tabs = self.tabs();
+        let current = tabs
+            .iter()
+            .position(|(category, _)| *category == self.category)
+            .unwrap_or(0);
+        let next = (current as isize + delta).rem_euclid(tabs.len() as isize);
+        self.category = tabs[next as usize].0;
+    }
+
+    /// Whether matching is still in progress, and the list may change
+    pub fn matching(&self) -> bool {
+        self.matching.is_some()
     }
 
     /// Set verbosity level
@@ -48,94 +294,1185 @@ impl<'a> UI<'a> {
     /// Update `self.info` to current selection
     ///
     /// Should be called every time `self.selected` changes
-    pub fn info(&mut self, color: Color) {
+    pub fn info(&mut self, name: Style) {
+        self.failed = false;
         if let Some(selected) = self.selected {
+            let app = self.app(selected);
             // If there's some selection, update info
-            self.text = vec![
-                Line::from(Span::styled(
-                    self.shown[selected].name.clone(),
-                    Style::default().fg(color),
-                )),
-                Line::from(Span::raw(self.shown[selected].description.clone())),
+            let mut text = vec![
+                Line::from(Span::styled(app.name.clone(), name)),
+                Line::from(Span::raw(app.description.clone())),
             ];
             if self.verbose > 1 {
-                self.text.push(Line::default());
+                text.push(Line::default());
 
-                let mut text = if self.shown[selected].is_terminal {
+                let mut exec = if app.is_terminal {
                     vec![Span::raw("Exec (terminal): ")]
                 } else {
                     vec![Span::raw("Exec: ")]
                 };
 
-                text.push(Span::styled(
-                    self.shown[selected].command.to_string(),
-                    Style::default(),
-                ));
+                exec.push(Span::styled(app.command.to_string(), Style::default()));
 
-                self.text.push(Line::from(text));
+                text.push(Line::from(exec));
 
                 if self.verbose > 2 {
-                    self.text.push(Line::from(Span::raw(format!(
+                    text.push(Line::from(Span::raw(format!(
                         "Times run: {}",
-                        &self.shown[selected].history
+                        &app.history
+                    ))));
+                    text.push(Line::from(Span::raw(format!(
+                        "Failed to start: {}",
+                        &app.failures
                     ))));
-                    self.text.push(Line::from(Span::raw(format!(
+                    text.push(Line::from(Span::raw(format!(
                         "\nMatching score: {}",
-                        self.shown[selected].score
+                        app.score
                     ))));
                 }
             }
+            self.text = text;
         } else {
             // Else, clear info
             self.text.clear();
         }
     }
 
-    /// Updates shown and hidden apps
+    /// `name`, with the characters matching the query in `style`
+    pub fn highlight(&self, name: &str, style: Style) -> Line<'static> {
+        let (_, query) = self.scope();
+        let positions = if query.is_empty() {
+            vec![]
+        } else if self.exact {
+            let (name, query) = (name.to_lowercase(), query.to_lowercase());
+            name.find(&query)
+                .map(|start| {
+                    let start = name[..start].chars().count();
+                    (start..start + query.chars().count()).collect()
+                })
+                .unwrap_or_default()
+        } else {
+            self.highlighter
+                .fuzzy_indices(name, query)
+                .map(|(_, positions)| positions)
+                .unwrap_or_default()
+        };
+
+        // Group the characters in spans, matching or not
+        let span = |text, matching| match matching {
+            true => Span::styled(text, style),
+            false => Span::raw(text),
+        };
+        let mut spans = vec![];
+        let mut text = String::new();
+        let mut matching = false;
+        for (i, c) in name.chars().enumerate() {
+            let matches = positions.binary_search(&i).is_ok();
+            if matches != matching && !text.is_empty() {
+                spans.push(span(std::mem::take(&mut text), matching));
+            }
+            matching = matches;
+            text.push(c);
+        }
+        spans.push(span(text, matching));
+
+        Line::from(spans)
+    }
+
+    /// Moves the selection `delta` apps down (or up, if negative), stopping at the ends of the
+    /// list
+    pub fn move_selection(&mut self, delta: isize) {
+        if let Some(selected) = self.selected {
+            self.selected = Some(
+                selected
+                    .saturating_add_signed(delta)
+                    .min(self.shown.len().saturating_sub(1)),
+            );
+        }
+    }
+
+    /// Updates `self.offset` so the selection is visible in a list `height` rows high, with at
+    /// least `scrolloff` apps above and below it
+    ///
+    /// The list only scrolls when the selection gets too close to an edge, like in Vim
+    pub fn scroll(&mut self, height: usize, scrolloff: usize) {
+        let Some(selected) = self.selected else {
+            self.offset = 0;
+            return;
+        };
+        if height == 0 {
+            // Nothing is visible, but the selection can't be above the list
+            self.offset = self.offset.min(selected);
+            return;
+        }
+
+        // The margins can't overlap
+        let scrolloff = scrolloff.min((height - 1) / 2);
+
+        if selected < self.offset + scrolloff {
+            self.offset = selected.saturating_sub(scrolloff);
+        } else if selected + scrolloff >= self.offset + height {
+            self.offset = selected + scrolloff + 1 - height;
+        }
+
+        // Don't leave empty rows at the end
+        self.offset = self.offset.min(self.shown.len().saturating_sub(height));
+    }
+
+    /// Shows `error` instead of the info text, until the selection changes
+    pub fn error(&mut self, error: &eyre::Report) {
+        self.failed = true;
+        let style = Style::default().fg(Color::Red);
+        self.text = vec![Line::from(Span::styled(
+            "Error",
+            style.add_modifier(Modifier::BOLD),
+        ))];
+        for cause in error.chain() {
+            for line in cause.to_string().lines() {
+                self.text
+                    .push(Line::from(Span::styled(line.to_string(), style)));
+            }
+        }
+    }
+
+    /// Replaces the apps read from `file` (or from any file inside, if it's a directory) with
+    /// `apps`
+    ///
+    /// Keeps the current query, and the selection if the selected app is still there. Waits a
+    /// bit for matching to finish, as the indices of the shown apps change
+    pub fn replace(&mut self, file: &path::Path, apps: Vec<xdg::App>) {
+        self.keep_selection(|ui| {
+            ui.apps.retain(|app| !app.file.starts_with(file));
+            // Indices change
+            ui.tree.expanded = None;
+            ui.apps.extend(apps);
+            ui.names_dirty = true;
+            ui.cache.clear();
+            ui.shown.clear();
+            ui.refilter(None);
+            ui.poll(REPLACE_WAIT);
+        });
+    }
+
+    /// Runs `change`, then selects the same app as before if it's still shown
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        // Indices don't survive `change`, so remember the app itself
+        let previous = self
+            .selected
+            .and_then(|i| self.shown.get(i))
+            .map(|i| self.apps[*i].clone());
+
+        change(self);
+
+        self.selected = if self.shown.is_empty() {
+            None
+        } else {
+            previous
+                .and_then(|previous| self.shown().position(|app| app.is_same(&previous)))
+                // Stay around the same position
+                .or_else(|| self.selected.map(|i| i.min(self.shown.len() - 1)))
+                .or(Some(0))
+        };
+    }
+
+    /// Selects the app at `index` of `self.apps` if it's shown, or the first one
+    fn select(&mut self, index: Option<usize>) {
+        self.selected = if self.shown.is_empty() {
+            // Can't select anything if there's no items
+            None
+        } else {
+            index
+                .and_then(|index| self.shown.iter().position(|i| *i == index))
+                .or(Some(0))
+        };
+    }
+
+    /// Updates shown apps
     ///
-    /// Matches using [`fuzzy_matcher`], with pattern being `self.query`
+    /// Matches using [`fuzzy_matcher`], or as a substring if `self.exact` is set, with pattern
+    /// being `self.query`. Big lists take longer than [`WAIT`] to match, the rest of the
+    /// matches come from [`UI::poll`]
     ///
     /// Should be called every time user adds/removes characters from `self.query`
     pub fn filter(&mut self) {
-        // Hide apps that do *not* match the current filter,
-        // and update score for the ones that do
-        let mut i = 0;
-        while i != self.shown.len() {
-            match self.matcher.fuzzy_match(&self.shown[i].name, &self.query) {
-                // No match. Set score to 0 and move to self.hidden
-                None => {
-                    self.shown[i].score = 0;
-                    self.hidden.push(self.shown.remove(i));
+        // The list changed, go to first item, unless the selected app is still there
+        let previous = if self.sticky {
+            self.selected.and_then(|i| self.shown.get(i)).copied()
+        } else {
+            None
+        };
+
+        let start = Instant::now();
+        if self.refilter(previous) {
+            self.select(previous);
+            self.filter_time = start.elapsed();
+        } else {
+            self.poll(WAIT);
+        }
+    }
+
+    /// Updates shown apps from the cache, or starts matching them. Returns whether it's done
+    ///
+    /// Anything matching a query also matches its prefixes, so only the results of the longest
+    /// cached prefix need to be scored again. The selection is left alone, unless matching
+    /// has to start: `keep` is selected when it's done, if it matched
+    fn refilter(&mut self, keep: Option<usize>) -> bool {
+        if self.exact != self.cache_exact {
+            self.cache.clear();
+            self.cache_exact = self.exact;
+        }
+
+        // Only borrow the query, the rest of `self` changes below
+        let (scope, query) = scope(self.query.as_str(), self.category);
+        while self
+            .cache
+            .last()
+            .is_some_and(|filtered| !query.starts_with(&filtered.query))
+        {
+            self.cache.pop();
+        }
+
+        let candidates = match self.cache.last() {
+            // Seen it before (the query got shorter), nothing to match
+            Some(filtered) if filtered.query == query => {
+                if self.matching.take().is_some() {
+                    self.matcher.cancel();
                 }
-                // Item matched query. Update score
-                Some(score) => {
-                    self.shown[i].score = score;
-                    i += 1;
+                for (i, score) in &filtered.shown {
+                    self.apps[*i].score = *score;
                 }
+                let search_actions = self.search_actions && !query.is_empty();
+                let (shown, counts) = (&mut self.shown, &mut self.counts);
+                let (tree, matches) = (&self.tree, &filtered.shown);
+                (self.matched, self.nested) = show(
+                    &self.apps,
+                    tree,
+                    scope,
+                    search_actions,
+                    matches,
+                    shown,
+                    counts,
+                );
+                return true;
             }
+            // The query got longer, narrow down the previous results, best ones first
+            Some(filtered) => Some(filtered.shown.iter().map(|(i, _)| *i).collect()),
+            None => None,
+        };
+
+        if self.names_dirty {
+            self.names = self.apps.iter().map(|app| app.name.clone()).collect();
+            self.categories = Category::ALL
+                .into_iter()
+                .filter(|category| {
+                    self.apps
+                        .iter()
+                        .any(|app| app.main_categories.contains(category))
+                })
+                .collect();
+            self.tree = Tree::new(&self.apps, self.tree.expanded);
+
+            // Comparing names is slow, so do it once. Fall back to the index, so equal apps
+            // stay put
+            let mut order = (0..self.apps.len()).collect::<Vec<_>>();
+            order.sort_unstable_by(|a, b| self.apps[*a].cmp_name(&self.apps[*b]).then(a.cmp(b)));
+            self.ranks.resize(order.len(), 0);
+            for (rank, i) in order.into_iter().enumerate() {
+                self.ranks[i] = rank;
+            }
+
+            self.names_dirty = false;
+        }
+        let generation = self
+            .matcher
+            .start(self.names.clone(), query, self.exact, candidates);
+        self.matching = Some(Matching {
+            generation,
+            query: query.to_string(),
+            matches: vec![],
+            keep,
+            first: true,
+            start: Instant::now(),
+        });
+        false
+    }
+
+    /// Stops matching, the shown apps stay as they are
+    fn cancel(&mut self) {
+        if self.matching.take().is_some() {
+            self.matcher.cancel();
         }
+    }
+
+    /// Shows the apps matched so far, waiting up to `timeout` for matching to finish. Returns
+    /// whether the list changed
+    ///
+    /// Should be called often while [`UI::matching`]. The selection stays at the top, or on the
+    /// same app if the user moved it
+    pub fn poll(&mut self, timeout: Duration) -> bool {
+        let (scope, query) = self.scope();
+        let search_actions = self.search_actions && !query.is_empty();
+        let Some(matching) = &mut self.matching else {
+            return false;
+        };
 
-        // Re-add hidden apps that *do* match the current filter, and update their score
-        i = 0;
-        while i != self.hidden.len() {
-            if let Some(score) = self.matcher.fuzzy_match(&self.hidden[i].name, &self.query) {
-                self.hidden[i].score = score;
-                self.shown.push(self.hidden.remove(i));
-            } else {
-                i += 1;
+        let deadline = Instant::now().checked_add(timeout);
+        // Take everything that's ready before sorting, sorting after every batch could take
+        // longer than the worker takes to send the next one
+        let mut matches = vec![];
+        let mut changed = false;
+        let mut done = false;
+        while let Some(batch) = self.matcher.recv(deadline) {
+            // Left from a cancelled job
+            if batch.generation != matching.generation {
+                continue;
+            }
+
+            matches.extend(batch.matches);
+            changed = true;
+            if batch.done {
+                done = true;
+                break;
             }
         }
+        if !changed {
+            return false;
+        }
 
-        // Sort the vector (should use our custom Cmp)
-        self.shown.sort();
+        for (i, score) in &matches {
+            self.apps[*i].score = *score;
+        }
+        // Sort like our custom Cmp does, by score and then by name
+        let (apps, ranks) = (&self.apps, &self.ranks);
+        let order = |(a, _): &(usize, i64), (b, _): &(usize, i64)| {
+            let score = |i: usize| apps[i].corrected_score();
+            score(*b).cmp(&score(*a)).then(ranks[*a].cmp(&ranks[*b]))
+        };
+        matches.sort_unstable_by(order);
+        // Both are sorted, so this only merges them
+        matching.matches.extend(matches);
+        matching.matches.sort_by(order);
 
-        // Reset selection to beginning (don't want to have the user go to the start
-        if self.shown.is_empty() {
-            // Can't select anything if there's no items
-            self.selected = None;
+        let keep = if matching.first {
+            matching.keep
         } else {
-            // The list changed, go to first item
-            self.selected = Some(0);
+            self.selected
+                .filter(|selected| *selected != 0)
+                .map(|selected| self.shown[selected])
+        };
+        matching.first = false;
+
+        let (shown, counts) = (&mut self.shown, &mut self.counts);
+        let (tree, matches) = (&self.tree, &matching.matches);
+        (self.matched, self.nested) = show(
+            &self.apps,
+            tree,
+            scope,
+            search_actions,
+            matches,
+            shown,
+            counts,
+        );
+        self.select(keep);
+
+        if done {
+            let matching = self.matching.take().expect("matching above");
+            self.filter_time = matching.start.elapsed();
+            self.cache.push(Filtered {
+                query: matching.query,
+                shown: matching.matches,
+            });
+        }
+        true
+    }
+}
+
+/// Splits the `cat:` prefix off `query`, see [`UI::scope`]
+fn scope(query: &str, category: Option<Category>) -> (Scope, &str) {
+    let Some(rest) = query.strip_prefix(CATEGORY_PREFIX) else {
+        return (category.map_or(Scope::All, Scope::Category), query);
+    };
+    let (name, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    let scope = match name {
+        // Nothing typed yet
+        "" => category.map_or(Scope::All, Scope::Category),
+        name => Category::find(name).map_or(Scope::Unknown, Scope::Category),
+    };
+    (scope, rest.trim_start())
+}
+
+/// Puts the `matches` in `scope` in `shown`, with the actions of the expanded app after it, and
+/// how many there are of each category in `counts`
+///
+/// Actions are left out, unless `search_actions` is set. Returns the number of matches, and where
+/// the actions of the expanded app are in `shown`
+fn show(
+    apps: &[xdg::App],
+    tree: &Tree,
+    scope: Scope,
+    search_actions: bool,
+    matches: &[(usize, i64)],
+    shown: &mut Vec<usize>,
+    counts: &mut [usize; Category::ALL.len()],
+) -> (usize, Range<usize>) {
+    // Actions of the expanded app go under it, if it's shown
+    let expanded = tree
+        .expanded
+        .filter(|app| scope.contains(&apps[*app]) && matches.iter().any(|(i, _)| i == app));
+
+    shown.clear();
+    *counts = [0; Category::ALL.len()];
+    let mut matched = 0;
+    let mut nested = 0..0;
+    for (i, _) in matches {
+        let parent = tree.parents[*i];
+        if parent.is_some() && (!search_actions || parent == expanded) {
+            continue;
+        }
+        matched += 1;
+        for category in &apps[*i].main_categories {
+            counts[*category as usize] += 1;
+        }
+        if scope.contains(&apps[*i]) {
+            shown.push(*i);
+        }
+        if expanded == Some(*i) {
+            nested = shown.len()..shown.len() + tree.children[i].len();
+            shown.extend(&tree.children[i]);
+        }
+    }
+    (matched, nested)
+}
+
+/// Desktop actions of each app
+#[derive(Default)]
+struct Tree {
+    /// App of each action, indexed like the app store. `None` for apps
+    parents: Vec<Option<usize>>,
+    /// Actions of each app with any, in the order of its desktop file
+    children: HashMap<usize, Vec<usize>>,
+    /// App whose actions are listed under it
+    expanded: Option<usize>,
+}
+
+impl Tree {
+    /// Finds the actions of each app in `apps`, keeping `expanded` if it still has some
+    fn new(apps: &[xdg::App], expanded: Option<usize>) -> Self {
+        // An app and its actions come from the same desktop file
+        let apps_by_file = apps
+            .iter()
+            .enumerate()
+            .filter(|(_, app)| app.action.is_none())
+            .map(|(i, app)| (&app.file, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut tree = Self::default();
+        for (i, app) in apps.iter().enumerate() {
+            let parent = match app.action {
+                Some(_) => apps_by_file.get(&app.file).copied(),
+                None => None,
+            };
+            if let Some(parent) = parent {
+                tree.children.entry(parent).or_default().push(i);
+            }
+            tree.parents.push(parent);
+        }
+        tree.expanded = expanded.filter(|app| tree.children.contains_key(app));
+        tree
+    }
+}
+
+/// Matching in progress
+struct Matching {
+    /// Job the matches come from
+    generation: usize,
+    /// The query being matched
+    query: String,
+    /// Matches so far, sorted
+    matches: Vec<(usize, i64)>,
+    /// App to select once it matches
+    keep: Option<usize>,
+    /// Whether no matches came yet
+    first: bool,
+    /// When it started
+    start: Instant,
+}
+
+/// Apps matching a query, with their scores
+struct Filtered {
+    /// The query they matched
+    query: String,
+    /// Indices of the matching apps and their scores, sorted
+    shown: Vec<(usize, i64)>,
+}
+
+/// Line editor for the query, with readline-like movement and kill/yank
+///
+/// The cursor always sits on a grapheme boundary.
+#[derive(Default)]
+pub struct Query {
+    /// Query text
+    text: String,
+    /// Cursor position, as a byte offset in `text`
+    cursor: usize,
+    /// Last killed text
+    yanked: String,
+}
+
+impl Query {
+    /// Query text
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Width of the text before the cursor, in terminal columns
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.cursor].width()
+    }
+
+    /// Inserts `c` at the cursor
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        // A combining character joins the previous grapheme, don't leave the cursor inside it
+        self.cursor = self.next_boundary(self.prev_boundary(self.cursor));
+    }
+
+    /// Removes the grapheme before the cursor. Returns whether the text changed
+    pub fn backspace(&mut self) -> bool {
+        let start = self.prev_boundary(self.cursor);
+        self.remove(start, self.cursor, false)
+    }
+
+    /// Removes the grapheme under the cursor. Returns whether the text changed
+    pub fn delete(&mut self) -> bool {
+        let end = self.next_boundary(self.cursor);
+        self.remove(self.cursor, end, false)
+    }
+
+    /// Moves the cursor one grapheme to the left
+    pub fn left(&mut self) {
+        self.cursor = self.prev_boundary(self.cursor);
+    }
+
+    /// Moves the cursor one grapheme to the right
+    pub fn right(&mut self) {
+        self.cursor = self.next_boundary(self.cursor);
+    }
+
+    /// Whether the cursor is at the start of the line
+    pub fn at_start(&self) -> bool {
+        self.cursor == 0
+    }
+
+    /// Whether the cursor is at the end of the line
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.text.len()
+    }
+
+    /// Moves the cursor to the start of the line
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// Moves the cursor to the end of the line
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Moves the cursor to the start of the current or previous word
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start(is_alphanumeric);
+    }
+
+    /// Moves the cursor to the end of the current or next word
+    pub fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    /// Kills from the cursor to the start of the word, words being separated by whitespace.
+    /// Returns whether the text changed
+    pub fn kill_word(&mut self) -> bool {
+        let start = self.word_start(|g| !g.trim().is_empty());
+        self.remove(start, self.cursor, true)
+    }
+
+    /// Kills from the cursor to the start of the word, words being alphanumeric. Returns whether
+    /// the text changed
+    pub fn kill_alphanumeric_word(&mut self) -> bool {
+        let start = self.word_start(is_alphanumeric);
+        self.remove(start, self.cursor, true)
+    }
+
+    /// Kills from the cursor to the start of the line. Returns whether the text changed
+    pub fn kill_to_start(&mut self) -> bool {
+        self.remove(0, self.cursor, true)
+    }
+
+    /// Kills from the cursor to the end of the line. Returns whether the text changed
+    pub fn kill_to_end(&mut self) -> bool {
+        self.remove(self.cursor, self.text.len(), true)
+    }
+
+    /// Removes everything, without saving it for [yank](Self::yank). Returns whether the text
+    /// changed
+    pub fn clear(&mut self) -> bool {
+        self.remove(0, self.text.len(), false)
+    }
+
+    /// Inserts the last killed text at the cursor. Returns whether the text changed
+    pub fn yank(&mut self) -> bool {
+        self.text.insert_str(self.cursor, &self.yanked);
+        self.cursor += self.yanked.len();
+        !self.yanked.is_empty()
+    }
+
+    /// Removes `start..end`, saving it for [yank](Self::yank) if `kill` is set
+    fn remove(&mut self, start: usize, end: usize, kill: bool) -> bool {
+        if start == end {
+            return false;
+        }
+
+        let removed = self.text.drain(start..end).collect();
+        if kill {
+            self.yanked = removed;
+        }
+        self.cursor = start;
+        true
+    }
+
+    /// Grapheme boundary before `at`
+    fn prev_boundary(&self, at: usize) -> usize {
+        self.text[..at]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// Grapheme boundary after `at`
+    fn next_boundary(&self, at: usize) -> usize {
+        self.text[at..]
+            .graphemes(true)
+            .next()
+            .map_or(at, |g| at + g.len())
+    }
+
+    /// Start of the word before the cursor, skipping what isn't part of a word first
+    fn word_start(&self, is_word: impl Fn(&str) -> bool) -> usize {
+        let mut graphemes = self.text[..self.cursor].grapheme_indices(true).rev();
+        let mut start = self.cursor;
+
+        for (i, g) in graphemes.by_ref() {
+            start = i;
+            if is_word(g) {
+                break;
+            }
+        }
+        for (i, g) in graphemes {
+            if !is_word(g) {
+                break;
+            }
+            start = i;
+        }
+
+        start
+    }
+
+    /// End of the word after the cursor, skipping what isn't part of a word first
+    fn word_end(&self) -> usize {
+        let mut graphemes = self.text[self.cursor..].graphemes(true);
+        let mut end = self.cursor;
+
+        for g in graphemes.by_ref() {
+            end += g.len();
+            if is_alphanumeric(g) {
+                break;
+            }
+        }
+        for g in graphemes {
+            if !is_alphanumeric(g) {
+                break;
+            }
+            end += g.len();
+        }
+
+        end
+    }
+}
+
+/// Whether the grapheme `g` is part of a word
+fn is_alphanumeric(g: &str) -> bool {
+    g.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// App called `name`, read from `file`
+    fn app(name: &str, file: &str) -> xdg::App {
+        let mut app = xdg::App::parse(
+            format!("[Desktop Entry]\nType=Application\nName={name}\nExec=true\n"),
+            None,
+        )
+        .unwrap();
+        app.file = file.into();
+        app
+    }
+
+    /// UI with an app called each of `names`, filtered with an empty query
+    fn new_ui(names: &[&str]) -> UI<'static> {
+        let apps = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| app(name, &format!("/apps/{i}.desktop")))
+            .collect();
+        let mut ui = UI::new(apps);
+        filter(&mut ui);
+        ui
+    }
+
+    /// Filters with the current query, waiting for matching to finish
+    fn filter(ui: &mut UI) {
+        ui.filter();
+        while ui.matching() {
+            ui.poll(Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn scroll() {
+        let names = (0..20).map(|i| format!("App {i:02}")).collect::<Vec<_>>();
+        let mut ui = new_ui(&names.iter().map(String::as_str).collect::<Vec<_>>());
+
+        ui.selected = Some(9);
+        ui.scroll(5, 1);
+        assert_eq!(ui.offset, 6);
+        // Only scrolls near the edges
+        ui.selected = Some(8);
+        ui.scroll(5, 1);
+        assert_eq!(ui.offset, 6);
+        ui.selected = Some(6);
+        ui.scroll(5, 1);
+        assert_eq!(ui.offset, 5);
+        // No empty rows at the end
+        ui.selected = Some(19);
+        ui.scroll(5, 3);
+        assert_eq!(ui.offset, 15);
+    }
+
+    #[test]
+    fn scroll_without_rows() {
+        let mut ui = new_ui(&["a", "b", "c", "d", "e", "f"]);
+        ui.selected = Some(5);
+        ui.scroll(2, 0);
+        assert_eq!(ui.offset, 4);
+
+        // The list has no room left, the offset still can't be past the selection
+        ui.selected = Some(1);
+        ui.scroll(0, 0);
+        assert!(ui.offset <= 1);
+    }
+
+    #[test]
+    fn move_selection() {
+        let mut ui = new_ui(&["a", "b", "c"]);
+        assert_eq!(ui.selected, Some(0));
+        ui.move_selection(2);
+        assert_eq!(ui.selected, Some(2));
+        // Stops at the ends
+        ui.move_selection(5);
+        assert_eq!(ui.selected, Some(2));
+        ui.move_selection(-5);
+        assert_eq!(ui.selected, Some(0));
+
+        let mut empty = new_ui(&[]);
+        assert_eq!(empty.selected, None);
+        empty.move_selection(1);
+        empty.scroll(5, 1);
+        assert_eq!((empty.selected, empty.offset), (None, 0));
+    }
+
+    /// Query with `text` typed in
+    fn query(text: &str) -> Query {
+        let mut query = Query::default();
+        text.chars().for_each(|c| query.insert(c));
+        query
+    }
+
+    /// Types `text` into the query of `ui`, filtering after each character like the UI does
+    fn type_in(ui: &mut UI, text: &str) {
+        for c in text.chars() {
+            ui.query.insert(c);
+            filter(ui);
         }
     }
+
+    /// Names of the shown apps
+    fn shown(ui: &UI) -> Vec<String> {
+        ui.shown().map(|app| app.name.clone()).collect()
+    }
+
+    #[test]
+    fn query_insert() {
+        let mut query = query("gimp");
+        assert_eq!((query.as_str(), query.at_end()), ("gimp", true));
+        query.left();
+        query.left();
+        query.insert('-');
+        assert_eq!(query.as_str(), "gi-mp");
+        query.home();
+        assert!(query.at_start());
+        query.insert('>');
+        assert_eq!(query.as_str(), ">gi-mp");
+        assert_eq!(query.cursor_width(), 1);
+    }
+
+    #[test]
+    fn query_graphemes() {
+        // "e" and a combining acute accent are one grapheme
+        let mut query = query("cafe\u{301}");
+        query.left();
+        assert_eq!(query.cursor_width(), 3);
+        query.right();
+        assert!(query.at_end());
+        assert!(query.backspace());
+        assert_eq!(query.as_str(), "caf");
+
+        // Wide characters take two columns
+        let query = self::query("日本");
+        assert_eq!(query.cursor_width(), 4);
+    }
+
+    #[test]
+    fn query_delete() {
+        let mut query = query("ab");
+        query.home();
+        assert!(!query.backspace());
+        assert!(query.delete());
+        assert_eq!(query.as_str(), "b");
+        query.end();
+        assert!(!query.delete());
+    }
+
+    #[test]
+    fn query_words() {
+        let mut query = query("libre office-writer");
+        query.word_left();
+        assert_eq!(query.cursor_width(), "libre office-".len());
+        query.word_left();
+        query.word_left();
+        assert!(query.at_start());
+        query.word_right();
+        assert_eq!(query.cursor_width(), "libre".len());
+        query.word_right();
+        assert_eq!(query.cursor_width(), "libre office".len());
+    }
+
+    #[test]
+    fn query_kill_yank() {
+        let mut query = query("libre office-writer");
+        // Alphanumeric words stop at the dash, whitespace separated ones don't
+        assert!(query.kill_alphanumeric_word());
+        assert_eq!(query.as_str(), "libre office-");
+        assert!(query.yank());
+        assert!(query.kill_word());
+        assert_eq!(query.as_str(), "libre ");
+        assert!(query.yank());
+        assert_eq!(query.as_str(), "libre office-writer");
+
+        query.home();
+        query.word_right();
+        assert!(query.kill_to_end());
+        assert_eq!(query.as_str(), "libre");
+        assert!(query.kill_to_start());
+        assert_eq!(query.as_str(), "");
+        assert!(!query.kill_to_start());
+        assert!(query.yank());
+        assert_eq!(query.as_str(), "libre");
+
+        // Clearing doesn't replace the killed text
+        assert!(query.clear());
+        assert!(query.yank());
+        assert_eq!(query.as_str(), "libre");
+    }
+
+    #[test]
+    fn cache_narrows() {
+        let mut ui = new_ui(&["Firefox", "Files", "Editor", "Calculator"]);
+        type_in(&mut ui, "fi");
+        assert_eq!(shown(&ui), ["Files", "Firefox"]);
+        // One entry per query, each a prefix of the next
+        let cached = ui
+            .cache
+            .iter()
+            .map(|f| f.query.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cached, ["", "f", "fi"]);
+
+        type_in(&mut ui, "r");
+        assert_eq!(shown(&ui), ["Firefox"]);
+        assert_eq!(ui.cache.len(), 4);
+    }
+
+    #[test]
+    fn cache_restores() {
+        let mut ui = new_ui(&["Firefox", "Files", "Editor", "Calculator"]);
+        type_in(&mut ui, "fir");
+
+        // Deleting shows the cached results right away
+        ui.query.backspace();
+        ui.filter();
+        assert!(!ui.matching());
+        assert_eq!(shown(&ui), ["Files", "Firefox"]);
+
+        // A query that isn't a longer one drops what it doesn't start with
+        ui.query.clear();
+        type_in(&mut ui, "ed");
+        assert_eq!(shown(&ui), ["Editor"]);
+        let cached = ui
+            .cache
+            .iter()
+            .map(|f| f.query.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cached, ["", "e", "ed"]);
+
+        // Switching the matcher starts over
+        ui.exact = true;
+        filter(&mut ui);
+        assert_eq!(shown(&ui), ["Editor"]);
+        assert_eq!(ui.cache.len(), 1);
+    }
+
+    #[test]
+    fn selection_resets() {
+        let mut ui = new_ui(&["Firefox", "Files", "Editor"]);
+        type_in(&mut ui, "f");
+        ui.selected = Some(1);
+        type_in(&mut ui, "i");
+        assert_eq!(ui.selected, Some(0));
+    }
+
+    #[test]
+    fn sticky_selection() {
+        let mut ui = new_ui(&["Firefox", "Files", "Editor"]);
+        ui.sticky = true;
+        type_in(&mut ui, "f");
+        assert_eq!(shown(&ui), ["Files", "Firefox"]);
+        ui.selected = Some(1);
+
+        // Still matches, stays selected
+        type_in(&mut ui, "i");
+        assert_eq!(ui.selected_app().unwrap().name, "Firefox");
+        ui.query.backspace();
+        filter(&mut ui);
+        assert_eq!(ui.selected_app().unwrap().name, "Firefox");
+
+        // Gone, back to the top
+        type_in(&mut ui, "l");
+        assert_eq!(shown(&ui), ["Files"]);
+        assert_eq!(ui.selected, Some(0));
+    }
+
+    #[test]
+    fn scope_prefix() {
+        assert_eq!(scope("gimp", None), (Scope::All, "gimp"));
+        assert_eq!(
+            scope("cat:gra gimp", None),
+            (Scope::Category(Category::Graphics), "gimp")
+        );
+        assert_eq!(
+            scope("cat:Graphics   gimp", None),
+            (Scope::Category(Category::Graphics), "gimp")
+        );
+        // The prefix wins over the tab
+        assert_eq!(
+            scope("cat:gra", Some(Category::Game)),
+            (Scope::Category(Category::Graphics), "")
+        );
+    }
+
+    #[test]
+    fn scope_empty() {
+        // Nothing typed after the prefix yet, the tab still applies
+        assert_eq!(scope("cat:", None), (Scope::All, ""));
+        assert_eq!(
+            scope("cat:", Some(Category::Game)),
+            (Scope::Category(Category::Game), "")
+        );
+        assert_eq!(
+            scope("cat: gimp", Some(Category::Game)),
+            (Scope::Category(Category::Game), "gimp")
+        );
+        assert_eq!(
+            scope("", Some(Category::Game)),
+            (Scope::Category(Category::Game), "")
+        );
+    }
+
+    #[test]
+    fn scope_unknown() {
+        assert_eq!(scope("cat:browser", None), (Scope::Unknown, ""));
+        assert_eq!(scope("cat:browser fire", None), (Scope::Unknown, "fire"));
+
+        let mut ui = new_ui(&["Firefox"]);
+        type_in(&mut ui, "cat:browser f");
+        assert!(ui.is_empty());
+        assert_eq!(ui.selected, None);
+    }
+
+    #[test]
+    fn scope_filters() {
+        let mut apps = vec![
+            app("Gimp", "/apps/gimp.desktop"),
+            app("Gnome Games", "/apps/games.desktop"),
+            app("Gedit", "/apps/gedit.desktop"),
+        ];
+        apps[0].main_categories = vec![Category::Graphics];
+        apps[1].main_categories = vec![Category::Game];
+        let mut ui = UI::new(apps);
+        filter(&mut ui);
+        assert_eq!(ui.len(), 3);
+
+        type_in(&mut ui, "cat:gr g");
+        assert_eq!(shown(&ui), ["Gimp"]);
+        assert_eq!(
+            ui.tabs(),
+            [
+                (None, 3),
+                (Some(Category::Game), 1),
+                (Some(Category::Graphics), 1)
+            ]
+        );
+
+        ui.query.clear();
+        ui.category = Some(Category::Game);
+        filter(&mut ui);
+        assert_eq!(shown(&ui), ["Gnome Games"]);
+    }
+
+    /// Firefox with two actions, between two apps without any
+    fn actions_ui() -> UI<'static> {
+        let action = |name: &str, action: &str| {
+            let mut app = app(name, "/apps/firefox.desktop");
+            app.action = Some(action.to_string());
+            app
+        };
+        let apps = vec![
+            app("Gedit", "/apps/gedit.desktop"),
+            action("New Window", "new-window"),
+            app("Firefox", "/apps/firefox.desktop"),
+            action("Private Window", "new-private-window"),
+            app("Atom", "/apps/atom.desktop"),
+        ];
+        let mut ui = UI::new(apps);
+        filter(&mut ui);
+        ui
+    }
+
+    #[test]
+    fn actions_hidden() {
+        let ui = actions_ui();
+        assert_eq!(shown(&ui), ["Atom", "Firefox", "Gedit"]);
+        assert_eq!(ui.expanded(0), None);
+        assert_eq!(ui.expanded(1), Some(false));
+        assert!((0..ui.len()).all(|i| !ui.nested(i)));
+    }
+
+    #[test]
+    fn expand_actions() {
+        let mut ui = actions_ui();
+        ui.selected = Some(1);
+        ui.toggle_actions();
+        // In the order of the desktop file
+        assert_eq!(
+            shown(&ui),
+            ["Atom", "Firefox", "New Window", "Private Window", "Gedit"]
+        );
+        assert_eq!(ui.expanded(1), Some(true));
+        assert_eq!(
+            (0..ui.len()).map(|i| ui.nested(i)).collect::<Vec<_>>(),
+            [false, false, true, true, false]
+        );
+        assert_eq!(ui.selected, Some(1));
+    }
+
+    #[test]
+    fn collapse_from_action() {
+        let mut ui = actions_ui();
+        ui.selected = Some(1);
+        ui.toggle_actions();
+        ui.selected = Some(3);
+        assert_eq!(ui.selected_app().unwrap().name, "Private Window");
+
+        ui.toggle_actions();
+        assert_eq!(shown(&ui), ["Atom", "Firefox", "Gedit"]);
+        assert_eq!(ui.expanded(1), Some(false));
+        // The app of the action, not whatever took its place
+        assert_eq!(ui.selected, Some(1));
+        assert_eq!(ui.selected_app().unwrap().name, "Firefox");
+    }
+
+    #[test]
+    fn toggle_without_actions() {
+        let mut ui = actions_ui();
+        ui.selected = Some(2);
+        ui.toggle_actions();
+        assert_eq!(shown(&ui), ["Atom", "Firefox", "Gedit"]);
+        assert_eq!(ui.selected, Some(2));
+
+        // Doesn't collapse another app either
+        ui.selected = Some(1);
+        ui.toggle_actions();
+        ui.selected = Some(4);
+        ui.toggle_actions();
+        assert_eq!(ui.len(), 5);
+        assert_eq!(ui.selected, Some(4));
+    }
+
+    #[test]
+    fn expanded_while_filtering() {
+        let mut ui = actions_ui();
+        ui.selected = Some(1);
+        ui.toggle_actions();
+
+        // Actions only come with their app
+        type_in(&mut ui, "ged");
+        assert_eq!(shown(&ui), ["Gedit"]);
+        assert!(!ui.nested(0));
+
+        ui.query.clear();
+        filter(&mut ui);
+        assert_eq!(
+            shown(&ui),
+            ["Atom", "Firefox", "New Window", "Private Window", "Gedit"]
+        );
+        assert!(ui.nested(2) && ui.nested(3));
+    }
+
+    #[test]
+    fn search_actions() {
+        let mut ui = actions_ui();
+        ui.search_actions = true;
+        // Not without a query
+        assert_eq!(ui.len(), 3);
+
+        type_in(&mut ui, "window");
+        assert_eq!(shown(&ui), ["New Window", "Private Window"]);
+        assert!(!ui.nested(0));
+
+        // The app isn't shown, so its actions stay where they are
+        ui.selected = Some(1);
+        ui.toggle_actions();
+        assert_eq!(shown(&ui), ["New Window", "Private Window"]);
+        assert_eq!(ui.selected_app().unwrap().name, "Private Window");
+
+        // Until it is
+        ui.query.clear();
+        filter(&mut ui);
+        assert_eq!(
+            shown(&ui),
+            ["Atom", "Firefox", "New Window", "Private Window", "Gedit"]
+        );
+    }
 }
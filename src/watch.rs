@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path;
+
+/// Events we care about: files being written, moved or removed, and directories (or symlinks)
+/// being created
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVED_FROM
+    | libc::IN_DELETE;
+
+/// Size of `struct inotify_event`, without the name
+const HEADER: usize = 16;
+
+/// A change in a watched directory
+#[derive(Debug)]
+pub enum Change {
+    /// A file was created, written or moved in
+    Changed(path::PathBuf),
+    /// A file or directory was removed or moved out
+    Removed(path::PathBuf),
+    /// A directory was created or moved in. It's watched too from now on
+    DirCreated(path::PathBuf),
+    /// Too many changes at once, some were lost. Every watched directory has to be read again
+    Overflowed,
+}
+
+/// Watches directories (recursive) using inotify
+pub struct Watcher {
+    inotify: fs::File,
+    /// Watch descriptors and the directory they watch
+    watches: HashMap<i32, path::PathBuf>,
+}
+
+impl Watcher {
+    /// Creates a new watcher, with no directories
+    pub fn new() -> io::Result<Self> {
+        // Safety: inotify_init1 has no preconditions
+        #[allow(unsafe_code)]
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Safety: fd is a valid file descriptor we own
+        #[allow(unsafe_code)]
+        let inotify = unsafe { fs::File::from_raw_fd(fd) };
+
+        Ok(Self {
+            inotify,
+            watches: HashMap::new(),
+        })
+    }
+
+    /// Watches `dir` and its subdirectories. Subdirectories that can't be watched are skipped
+    pub fn add(&mut self, dir: &path::Path) -> io::Result<()> {
+        let raw = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        // Safety: the file descriptor is valid, and raw is NUL terminated
+        #[allow(unsafe_code)]
+        let wd = unsafe { libc::inotify_add_watch(self.inotify.as_raw_fd(), raw.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.watches.insert(wd, dir.to_path_buf());
+
+        for entry in fs::read_dir(dir)?.flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                // Like an unreadable directory when listing files, don't give up on the rest
+                self.add(&entry.path()).ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the next batch of changes
+    pub fn wait(&mut self) -> io::Result<Vec<Change>> {
+        let mut buffer = [0u8; 4096];
+        let len = self.inotify.read(&mut buffer)?;
+
+        let mut changes = vec![];
+        let mut overflowed = false;
+        let mut offset = 0;
+
+        while offset + HEADER <= len {
+            let field = |n: usize| {
+                let start = offset + n * 4;
+                u32::from_ne_bytes(buffer[start..start + 4].try_into().unwrap())
+            };
+            let wd = field(0) as i32;
+            let mask = field(1);
+            let name_len = field(3) as usize;
+
+            // The name is NUL padded
+            let name = &buffer[offset + HEADER..offset + HEADER + name_len];
+            let name = OsStr::from_bytes(name.split(|b| *b == 0).next().unwrap_or_default());
+            offset += HEADER + name_len;
+
+            if mask & libc::IN_Q_OVERFLOW != 0 {
+                overflowed = true;
+                continue;
+            }
+            if mask & libc::IN_IGNORED != 0 {
+                // The directory was removed
+                self.watches.remove(&wd);
+                continue;
+            }
+
+            let Some(dir) = self.watches.get(&wd) else {
+                continue;
+            };
+            let path = dir.join(name);
+
+            if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                changes.push(Change::Removed(path));
+            } else if mask & libc::IN_ISDIR != 0 {
+                self.add(&path).ok();
+                changes.push(Change::DirCreated(path));
+            } else if mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0 {
+                changes.push(Change::Changed(path));
+            } else if mask & libc::IN_CREATE != 0 && path.is_symlink() {
+                // New files are only read once they're written, but symlinks are never written
+                changes.push(Change::Changed(path));
+            }
+        }
+
+        if overflowed {
+            // Directories created meanwhile may not be watched yet
+            let dirs = self.watches.values().cloned().collect::<Vec<_>>();
+            for dir in dirs {
+                self.add(&dir).ok();
+            }
+            changes = vec![Change::Overflowed];
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory for a test called `name`
+    fn temp_dir(name: &str) -> path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gyr-watch-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn changes() {
+        let dir = temp_dir("changes");
+        let mut watcher = Watcher::new().unwrap();
+        watcher.add(&dir).unwrap();
+
+        // Only once, when it's written
+        let file = dir.join("app.desktop");
+        fs::write(&file, "").unwrap();
+        let changes = watcher.wait().unwrap();
+        assert!(matches!(&changes[..], [Change::Changed(path)] if *path == file));
+
+        let sub = dir.join("sub");
+        fs::create_dir(&sub).unwrap();
+        let changes = watcher.wait().unwrap();
+        assert!(matches!(&changes[..], [Change::DirCreated(path)] if *path == sub));
+
+        // The new directory is watched too
+        let nested = sub.join("nested.desktop");
+        fs::write(&nested, "").unwrap();
+        let changes = watcher.wait().unwrap();
+        assert!(matches!(&changes[..], [Change::Changed(path)] if *path == nested));
+
+        let link = dir.join("link.desktop");
+        std::os::unix::fs::symlink(&nested, &link).unwrap();
+        let changes = watcher.wait().unwrap();
+        assert!(matches!(&changes[..], [Change::Changed(path)] if *path == link));
+
+        fs::remove_file(&file).unwrap();
+        let changes = watcher.wait().unwrap();
+        assert!(matches!(&changes[..], [Change::Removed(path)] if *path == file));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,93 +1,389 @@
+use std::collections::HashMap;
 use std::convert::{AsRef, TryInto};
+use std::env;
 use std::fmt;
 use std::fs;
 use std::path;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 use eyre::eyre;
 use ratatui::widgets::ListItem;
 use safe_regex::{regex, Matcher1};
+use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
+use super::cache::Cache;
+use super::icon::Icons;
+#[cfg(target_os = "linux")]
+use super::watch::{Change, Watcher};
+
+/// Name of the sled tree counting failed launches
+const FAILURES_TREE: &str = "failures";
+
+/// Maximum number of threads parsing desktop files
+const MAX_WORKERS: usize = 4;
+
+/// Size of the bounded queues between the desktop file parsers
+const QUEUE_SIZE: usize = 64;
+
+/// Launch history, stored in the database
+///
+/// Counts how many times each app was run, and how many times it failed to start
+#[derive(Clone)]
 pub struct AppHistory {
     db: sled::Db,
+    failures: sled::Tree,
 }
 
 impl AppHistory {
+    /// Opens the history in `db`
+    pub fn new(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            db: db.clone(),
+            failures: db.open_tree(FAILURES_TREE)?,
+        })
+    }
+
+    /// Fills in the history of `app`
     pub fn get(&self, app: App) -> App {
         let mut app = app;
-        if let Some(packed) = self.db.get(app.name.as_bytes()).unwrap() {
-            let unpacked = super::bytes::unpack(
+        app.history = Self::read(&self.db, &app.name);
+        app.failures = Self::read(&self.failures, &app.name);
+        app
+    }
+
+    /// Bumps the number of times `app` was run, returning the new value
+    pub fn launched(&self, app: &App) -> sled::Result<u64> {
+        Self::write(&self.db, &app.name, app.history + 1)
+    }
+
+    /// Bumps the number of times `app` failed to start, returning the new value
+    pub fn failed(&self, app: &App) -> sled::Result<u64> {
+        Self::write(&self.failures, &app.name, app.failures + 1)
+    }
+
+    /// Forgets everything
+    pub fn clear(&self) -> sled::Result<()> {
+        self.db.clear()?;
+        self.failures.clear()
+    }
+
+    /// Reads a counter from `tree`
+    fn read(tree: &sled::Tree, name: &str) -> u64 {
+        if let Some(packed) = tree.get(name.as_bytes()).unwrap() {
+            super::bytes::unpack(
                 packed
                     .as_ref()
                     .try_into()
                     .expect("Invalid data stored in database"),
-            );
-            app.history = unpacked;
+            )
+        } else {
+            0
+        }
+    }
+
+    /// Writes a counter to `tree`
+    fn write(tree: &sled::Tree, name: &str, value: u64) -> sled::Result<u64> {
+        let packed = super::bytes::pack(value);
+        tree.insert(name.as_bytes(), &packed)?;
+        Ok(value)
+    }
+}
+
+/// Directories to look for applications
+///
+/// Read from `$XDG_DATA_DIRS`, with a fallback to the default data directories.
+pub fn data_dirs() -> eyre::Result<Vec<path::PathBuf>> {
+    let mut dirs: Vec<path::PathBuf> = vec![];
+    if let Ok(res) = env::var("XDG_DATA_DIRS") {
+        for data_dir in res.split(':') {
+            let mut dir = path::PathBuf::from(data_dir);
+            dir.push("applications");
+            if dir.exists() {
+                dirs.push(dir.clone());
+            }
+        }
+    } else {
+        for data_dir in &mut [
+            // Data directories
+            path::PathBuf::from("/usr/share"),
+            path::PathBuf::from("/usr/local/share"),
+            dirs::data_local_dir().ok_or_else(|| eyre!("failed to get local data dir"))?,
+        ] {
+            // Add `/applications`
+            data_dir.push("applications");
+            if data_dir.exists() {
+                dirs.push(data_dir.clone());
+            }
         }
-        app
     }
+    Ok(dirs)
+}
+
+/// Change in the list of applications, sent by [read]
+#[derive(Debug)]
+// Almost every event is an `Add`, boxing the app would only cost an allocation per app
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    /// An app was found during the initial scan
+    Add(App),
+    /// A desktop file was created or changed, replace its apps with these. Can be a whole
+    /// directory too, when it had to be read again
+    Update(path::PathBuf, Vec<App>),
+    /// A desktop file, or a directory with desktop files, was removed
+    Remove(path::PathBuf),
+    /// The initial scan finished
+    Loaded,
 }
 
 /// Find XDG applications in `dirs` (recursive).
 ///
-/// Spawns a new thread and sends apps via a mpsc [Receiver]
+/// Spawns a new thread and sends apps via a mpsc [Receiver], followed by [Event::Loaded]
+///
+/// If `watch` is set, keeps watching `dirs` for changes afterwards (Linux only). Directories
+/// that can't be watched are skipped, and reported if `verbose` is set
 ///
 /// Updates history using the database
 ///
 /// [Receiver]: std::sync::mpsc::Receiver
-pub fn read(dirs: Vec<impl Into<path::PathBuf>>, db: &sled::Db) -> mpsc::Receiver<App> {
+pub fn read(
+    dirs: Vec<impl Into<path::PathBuf>>,
+    db: &sled::Db,
+    watch: bool,
+    verbose: bool,
+) -> mpsc::Receiver<Event> {
     let (sender, receiver) = mpsc::channel();
 
     let dirs: Vec<path::PathBuf> = dirs.into_iter().map(Into::into).collect();
-    let db = AppHistory { db: db.clone() };
+    let db = AppHistory::new(db).expect("Failed to open history database");
+
+    // Start watching before the initial scan, so no changes are missed. Errors are printed here,
+    // before the terminal is taken over
+    #[cfg(target_os = "linux")]
+    let watcher = watch.then(|| watcher(&dirs, verbose)).flatten();
+    #[cfg(not(target_os = "linux"))]
+    let _ = (watch, verbose);
 
     let _worker = thread::spawn(move || {
-        for dir in dirs {
-            let mut files: Vec<path::PathBuf> = vec![];
+        // Without a cache, every file gets parsed
+        let cache = Cache::open(&db.db).ok();
 
-            for entry in WalkDir::new(&dir)
-                .min_depth(1)
+        let files = |dir: &path::Path| match &cache {
+            Some(cache) => cache.files(dir),
+            None => walk(dir),
+        };
+        let parse = |file: &path::Path| -> Vec<App> {
+            let apps = match &cache {
+                Some(cache) => cache.apps(file, parse_file),
+                None => parse_file(file),
+            };
+            apps.into_iter().map(|app| db.get(app)).collect()
+        };
+
+        let all_files = dirs.iter().flat_map(|dir| files(dir)).collect();
+        let mut open = true;
+        parse_all(all_files, &parse, |apps| {
+            open = apps
                 .into_iter()
-                .filter(|entry| {
-                    if let Ok(path) = entry {
-                        if !path.file_type().is_dir() {
-                            return true;
+                .all(|app| sender.send(Event::Add(app)).is_ok());
+            open
+        });
+
+        if !open || sender.send(Event::Loaded).is_err() {
+            return;
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(mut watcher) = watcher {
+            while let Ok(changes) = watcher.wait() {
+                for change in changes {
+                    let events = match change {
+                        Change::Changed(file) => {
+                            let apps = parse(&file);
+                            vec![Event::Update(file, apps)]
+                        }
+                        Change::Removed(path) => vec![Event::Remove(path)],
+                        Change::DirCreated(dir) => walk(&dir)
+                            .into_iter()
+                            .map(|file| {
+                                let apps = parse(&file);
+                                Event::Update(file, apps)
+                            })
+                            .collect(),
+                        // Missed some changes, read everything again
+                        Change::Overflowed => dirs
+                            .iter()
+                            .map(|dir| {
+                                let apps = files(dir).into_iter().flat_map(|file| parse(&file));
+                                Event::Update(dir.clone(), apps.collect())
+                            })
+                            .collect(),
+                    };
+
+                    for event in events {
+                        if sender.send(event).is_err() {
+                            return;
                         }
                     }
-                    false
-                })
-                .map(Result::unwrap)
-            {
-                files.push(entry.path().to_owned());
+                }
             }
+        }
+    });
 
-            for file in &files {
-                if let Ok(contents) = fs::read_to_string(file) {
-                    if let Ok(app) = App::parse(&contents, None) {
-                        if let Some(actions) = &app.actions {
-                            for action in actions {
-                                let ac = Action::default().name(action).from(app.name.clone());
-                                if let Ok(a) = App::parse(&contents, Some(&ac)) {
-                                    sender.send(db.get(a)).unwrap();
-                                }
-                            }
-                        }
+    receiver
+}
 
-                        sender.send(db.get(app)).unwrap();
-                    }
+/// Watches `dirs`, skipping the ones that can't be watched. Errors are printed if `verbose` is set
+#[cfg(target_os = "linux")]
+fn watcher(dirs: &[path::PathBuf], verbose: bool) -> Option<Watcher> {
+    let mut watcher = Watcher::new()
+        .map_err(|error| {
+            if verbose {
+                eprintln!("Failed to watch application directories: {error}");
+            }
+        })
+        .ok()?;
+    for dir in dirs {
+        if let Err(error) = watcher.add(dir) {
+            if verbose {
+                eprintln!("Failed to watch {}: {error}", dir.display());
+            }
+        }
+    }
+    Some(watcher)
+}
+
+/// Parses `files` with a small pool of workers, calling `emit` with the apps in each of them.
+///
+/// Apps are emitted in the same order as `files`, so the result is the same as parsing them one
+/// by one. Stops early if `emit` returns `false`
+pub fn parse_all<P, E>(files: Vec<path::PathBuf>, parse: &P, mut emit: E)
+where
+    P: Fn(&path::Path) -> Vec<App> + Sync,
+    E: FnMut(Vec<App>) -> bool,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_WORKERS);
+
+    let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, path::PathBuf)>(QUEUE_SIZE);
+    let (result_sender, result_receiver) = mpsc::sync_channel::<(usize, Vec<App>)>(QUEUE_SIZE);
+    // Dropped along with the last worker, so the feeder stops if we stop early
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let jobs = Arc::clone(&job_receiver);
+            let results = result_sender.clone();
+            scope.spawn(move || loop {
+                let job = jobs.lock().unwrap().recv();
+                let Ok((i, file)) = job else {
+                    break;
+                };
+                if results.send((i, parse(&file))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(job_receiver);
+        drop(result_sender);
+
+        // Feed the workers from another thread, so the bounded queues can't deadlock
+        scope.spawn(move || {
+            for job in files.into_iter().enumerate() {
+                if job_sender.send(job).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Results arrive out of order. Hold them until all the previous ones are emitted
+        let mut pending = HashMap::new();
+        let mut next = 0;
+        'receive: for (i, apps) in &result_receiver {
+            pending.insert(i, apps);
+            while let Some(apps) = pending.remove(&next) {
+                next += 1;
+                if !emit(apps) {
+                    break 'receive;
                 }
             }
         }
-        drop(sender);
+        drop(result_receiver);
     });
+}
 
-    receiver
+/// Finds the files in `dir` (recursive)
+///
+/// In the same order as the cache lists them: the files of each directory sorted by name, then
+/// its subdirectories
+fn walk(dir: &path::Path) -> Vec<path::PathBuf> {
+    let mut files: Vec<path::PathBuf> = vec![];
+
+    for entry in WalkDir::new(dir)
+        .min_depth(1)
+        .sort_by(|a, b| {
+            let is_dir = |entry: &walkdir::DirEntry| entry.file_type().is_dir();
+            is_dir(a)
+                .cmp(&is_dir(b))
+                .then_with(|| a.file_name().cmp(b.file_name()))
+        })
+        .into_iter()
+        .filter(|entry| {
+            if let Ok(path) = entry {
+                if !path.file_type().is_dir() {
+                    return true;
+                }
+            }
+            false
+        })
+        .map(Result::unwrap)
+    {
+        files.push(entry.path().to_owned());
+    }
+
+    files
+}
+
+/// Parses the app in `file`, and its actions
+pub fn parse_file(file: &path::Path) -> Vec<App> {
+    let mut apps = vec![];
+
+    if let Ok(contents) = fs::read_to_string(file) {
+        if let Ok(app) = App::parse(&contents, None) {
+            if let Some(actions) = &app.actions {
+                for action in actions {
+                    let ac = Action::default().name(action).from(app.name.clone());
+                    if let Ok(a) = App::parse(&contents, Some(&ac)) {
+                        apps.push(a);
+                    }
+                }
+            }
+
+            // Actions without their own icon use the app's, and are in the same categories
+            for action in &mut apps {
+                if action.icon.is_none() {
+                    action.icon = app.icon.clone();
+                }
+                action.categories = app.categories.clone();
+                action.main_categories = app.main_categories.clone();
+                action.startup_wm_class = app.startup_wm_class.clone();
+            }
+
+            apps.push(app);
+        }
+    }
+
+    for app in &mut apps {
+        app.file = file.to_path_buf();
+    }
+
+    apps
 }
 
 /// An XDG Specification App
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct App {
     /// App name
     pub name: String,
@@ -99,6 +395,20 @@ pub struct App {
     pub is_terminal: bool,
     /// Path from which to run the command
     pub path: Option<String>,
+    /// Icon name or path
+    pub icon: Option<String>,
+    /// Categories the app belongs to, most generic first
+    pub categories: Vec<String>,
+    /// Main categories of [`categories`](Self::categories), sorted
+    pub main_categories: Vec<Category>,
+    /// WM class of the app's windows
+    pub startup_wm_class: Option<String>,
+    /// Name of the desktop action, if this is one. [`name`](Self::name) has the app's name too
+    pub action: Option<String>,
+    /// Desktop file the app was read from
+    ///
+    /// Not part of the specification
+    pub file: path::PathBuf,
     /// Matching score (used in [UI](super::ui::UI))
     ///
     /// Not part of the specification
@@ -107,6 +417,10 @@ pub struct App {
     ///
     /// Not part of the specification
     pub history: u64,
+    /// Number of times this app failed to start
+    ///
+    /// Not part of the specification
+    pub failures: u64,
 
     // This is not pub because I use it only on this file
     #[doc(hidden)]
@@ -114,6 +428,42 @@ pub struct App {
 }
 
 impl App {
+    /// Whether `self` and `other` are the same entry, maybe parsed from different versions of
+    /// its desktop file
+    pub fn is_same(&self, other: &App) -> bool {
+        self.file == other.file && self.name == other.name
+    }
+
+    /// Path of the icon closest to `size` pixels, looked up in `icons`
+    pub fn icon_path(&self, icons: &mut Icons, size: u32) -> Option<path::PathBuf> {
+        icons.find(self.icon.as_deref()?, size)
+    }
+
+    /// Desktop file ID: path of the file under `applications`, with `-` instead of `/`,
+    /// without `.desktop`
+    pub fn id(&self) -> String {
+        let components = self
+            .file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        let start = components
+            .iter()
+            .rposition(|c| c == "applications")
+            .map_or(components.len().saturating_sub(1), |i| i + 1);
+        let id = components[start..].join("-");
+        match id.strip_suffix(".desktop") {
+            Some(id) => id.to_string(),
+            None => id,
+        }
+    }
+
+    /// Compares names case-insensitively, without allocating lowercase copies
+    pub fn cmp_name(&self, other: &App) -> std::cmp::Ordering {
+        let other = other.name.chars().flat_map(char::to_lowercase);
+        self.name.chars().flat_map(char::to_lowercase).cmp(other)
+    }
+
     /// Returns a corrected score, mix of history and matching score
     pub fn corrected_score(&self) -> i64 {
         if self.history < 1 {
@@ -134,7 +484,7 @@ impl Ord for App {
             .cmp(&other.corrected_score())
             .reverse()
             // Then sort alphabetically
-            .then(self.name.to_lowercase().cmp(&other.name.to_lowercase()))
+            .then_with(|| self.cmp_name(other))
     }
 }
 
@@ -185,10 +535,14 @@ impl App {
         };
 
         let mut name = None;
+        let mut action_name = None;
         let mut exec = None;
         let mut description = None;
         let mut terminal_exec = false;
         let mut path = None;
+        let mut icon = None;
+        let mut categories = vec![];
+        let mut startup_wm_class = None;
         let mut actions = None;
 
         let mut search = false;
@@ -207,6 +561,7 @@ impl App {
                     let line = line.trim_start_matches("Name=");
                     if let Some(a) = &action {
                         name = Some(format!("{} ({})", &a.from, line));
+                        action_name = Some(line.to_string());
                     } else {
                         name = Some(line.to_string());
                     }
@@ -239,6 +594,19 @@ impl App {
                 } else if line.starts_with("Path=") && path.is_none() {
                     let line = line.trim_start_matches("Path=");
                     path = Some(line.to_string());
+                } else if line.starts_with("Icon=") && icon.is_none() {
+                    let line = line.trim_start_matches("Icon=");
+                    icon = Some(line.to_string());
+                } else if line.starts_with("Categories=") && categories.is_empty() {
+                    let line = line.trim_start_matches("Categories=");
+                    categories = line
+                        .split(';')
+                        .filter(|category| !category.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                } else if line.starts_with("StartupWMClass=") && startup_wm_class.is_none() {
+                    let line = line.trim_start_matches("StartupWMClass=");
+                    startup_wm_class = Some(line.to_string());
                 } else if line.starts_with("Actions=") && actions.is_none() && action.is_none() {
                     let line = line.trim_start_matches("Actions=");
                     let vector = line
@@ -262,16 +630,175 @@ impl App {
         Ok(App {
             score: 0,
             history: 0,
+            failures: 0,
             name,
             command: exec,
             description,
             is_terminal: terminal_exec,
             path,
+            icon,
+            main_categories: Category::all_of(&categories),
+            categories,
+            startup_wm_class,
+            action: action_name,
+            file: path::PathBuf::new(),
             actions,
         })
     }
 }
 
+/// Main categories of the freedesktop menu specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Category {
+    /// Multimedia
+    AudioVideo,
+    /// Audio
+    Audio,
+    /// Video
+    Video,
+    /// Programming
+    Development,
+    /// Learning
+    Education,
+    /// Games
+    Game,
+    /// Viewing, creating or processing graphics
+    Graphics,
+    /// Internet
+    Network,
+    /// Office work
+    Office,
+    /// Scientific software
+    Science,
+    /// Settings
+    Settings,
+    /// System administration
+    System,
+    /// Accessories
+    Utility,
+}
+
+/// Additional categories whose main category the desktop file might leave out
+const ADDITIONAL: &[(&str, Category)] = &[
+    ("WebBrowser", Category::Network),
+    ("Email", Category::Network),
+    ("Chat", Category::Network),
+    ("InstantMessaging", Category::Network),
+    ("IRCClient", Category::Network),
+    ("FileTransfer", Category::Network),
+    ("P2P", Category::Network),
+    ("IDE", Category::Development),
+    ("Debugger", Category::Development),
+    ("RevisionControl", Category::Development),
+    ("WordProcessor", Category::Office),
+    ("Spreadsheet", Category::Office),
+    ("Presentation", Category::Office),
+    ("Calendar", Category::Office),
+    ("Viewer", Category::Graphics),
+    ("RasterGraphics", Category::Graphics),
+    ("VectorGraphics", Category::Graphics),
+    ("Photography", Category::Graphics),
+    ("Player", Category::AudioVideo),
+    ("Recorder", Category::AudioVideo),
+    ("Music", Category::Audio),
+    ("TV", Category::Video),
+    ("TerminalEmulator", Category::System),
+    ("FileManager", Category::System),
+    ("Monitor", Category::System),
+    ("PackageManager", Category::System),
+    ("TextEditor", Category::Utility),
+    ("Calculator", Category::Utility),
+    ("Archiving", Category::Utility),
+    ("Math", Category::Science),
+    ("Astronomy", Category::Science),
+    ("Chemistry", Category::Science),
+    ("Physics", Category::Science),
+    ("Emulator", Category::Game),
+    ("ArcadeGame", Category::Game),
+    ("StrategyGame", Category::Game),
+];
+
+impl Category {
+    /// Every main category, in the order of the specification
+    pub const ALL: [Category; 13] = [
+        Category::AudioVideo,
+        Category::Audio,
+        Category::Video,
+        Category::Development,
+        Category::Education,
+        Category::Game,
+        Category::Graphics,
+        Category::Network,
+        Category::Office,
+        Category::Science,
+        Category::Settings,
+        Category::System,
+        Category::Utility,
+    ];
+
+    /// Name in desktop files
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::AudioVideo => "AudioVideo",
+            Category::Audio => "Audio",
+            Category::Video => "Video",
+            Category::Development => "Development",
+            Category::Education => "Education",
+            Category::Game => "Game",
+            Category::Graphics => "Graphics",
+            Category::Network => "Network",
+            Category::Office => "Office",
+            Category::Science => "Science",
+            Category::Settings => "Settings",
+            Category::System => "System",
+            Category::Utility => "Utility",
+        }
+    }
+
+    /// The category called `prefix`, or else the first one whose name starts with it, ignoring
+    /// case
+    pub fn find(prefix: &str) -> Option<Category> {
+        let prefix = prefix.to_lowercase();
+        let name = |category: &Category| category.name().to_lowercase();
+        // Every prefix of Audio is one of AudioVideo too
+        Self::ALL
+            .into_iter()
+            .find(|category| name(category) == prefix)
+            .or_else(|| {
+                Self::ALL
+                    .into_iter()
+                    .find(|category| name(category).starts_with(&prefix))
+            })
+    }
+
+    /// Main categories of `categories` (as written in a desktop file), sorted
+    fn all_of(categories: &[String]) -> Vec<Category> {
+        let mut main = categories
+            .iter()
+            .filter_map(|name| {
+                Self::ALL
+                    .into_iter()
+                    .find(|category| category.name() == name)
+                    .or_else(|| {
+                        ADDITIONAL
+                            .iter()
+                            .find(|(additional, _)| additional == name)
+                            .map(|(_, category)| *category)
+                    })
+            })
+            .collect::<Vec<_>>();
+        main.sort_unstable();
+        main.dedup();
+        main
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// An app action
 ///
 /// In gyr every action is some app, with the action name in parentheses
@@ -296,3 +823,113 @@ impl Action {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_all_order() {
+        let files = (0..QUEUE_SIZE * 4)
+            .map(|i| path::PathBuf::from(i.to_string()))
+            .collect::<Vec<_>>();
+        // Some files take longer, so the results arrive out of order
+        let parse = |file: &path::Path| {
+            let i: usize = file.to_str().unwrap().parse().unwrap();
+            if i.is_multiple_of(7) {
+                thread::sleep(std::time::Duration::from_millis(1));
+            }
+            let contents = format!("[Desktop Entry]\nType=Application\nName={i}\nExec=true\n");
+            vec![App::parse(contents, None).unwrap()]
+        };
+
+        let mut names = vec![];
+        parse_all(files.clone(), &parse, |apps| {
+            names.extend(apps.into_iter().map(|app| app.name));
+            true
+        });
+        let files = files.iter().map(|file| file.to_str().unwrap().to_string());
+        assert_eq!(names, files.collect::<Vec<_>>());
+
+        // Stops early
+        let mut emitted = 0;
+        parse_all(
+            (0..QUEUE_SIZE * 4).map(|i| i.to_string().into()).collect(),
+            &parse,
+            |_| {
+                emitted += 1;
+                emitted < 10
+            },
+        );
+        assert_eq!(emitted, 10);
+    }
+
+    #[test]
+    fn walk_order() {
+        let dir = std::env::temp_dir().join(format!("gyr-walk-{}", std::process::id()));
+        fs::create_dir_all(dir.join("a")).unwrap();
+        for file in ["b.desktop", "a/c.desktop", "a.desktop"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let files = walk(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let files = files.iter().map(|file| file.strip_prefix(&dir).unwrap());
+        assert_eq!(
+            files.collect::<Vec<_>>(),
+            ["a.desktop", "b.desktop", "a/c.desktop"].map(path::Path::new)
+        );
+    }
+
+    #[test]
+    fn find_category() {
+        assert_eq!(Category::find("gra"), Some(Category::Graphics));
+        assert_eq!(Category::find("GRAPHICS"), Some(Category::Graphics));
+        // The first one in the specification
+        assert_eq!(Category::find("s"), Some(Category::Science));
+        assert_eq!(Category::find("aud"), Some(Category::AudioVideo));
+        // Unless the name is complete
+        assert_eq!(Category::find("audio"), Some(Category::Audio));
+        assert_eq!(Category::find("graphicsx"), None);
+        assert_eq!(Category::find("browser"), None);
+    }
+
+    #[test]
+    fn main_categories() {
+        let categories = |names: &[&str]| {
+            Category::all_of(
+                &names
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            categories(&["Utility", "Development"]),
+            [Category::Development, Category::Utility]
+        );
+        // Additional categories count for their main one, once
+        assert_eq!(
+            categories(&["WebBrowser", "Network", "Email"]),
+            [Category::Network]
+        );
+        assert_eq!(categories(&["GTK", "TextEditor"]), [Category::Utility]);
+        // Names are case-sensitive, like in the specification
+        assert!(categories(&["utility", "X-Custom"]).is_empty());
+    }
+
+    #[test]
+    fn additional_categories() {
+        for (i, (name, _)) in ADDITIONAL.iter().enumerate() {
+            assert!(
+                Category::ALL
+                    .iter()
+                    .all(|category| category.name() != *name),
+                "{name} is a main category"
+            );
+            assert!(
+                ADDITIONAL[i + 1..].iter().all(|(other, _)| other != name),
+                "{name} is listed twice"
+            );
+        }
+    }
+}